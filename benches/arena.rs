@@ -0,0 +1,136 @@
+//! Compares parsing into an ast::Program with parsing into an arena::Tree:
+//! the time to parse and walk a program, and the memory its tree takes.
+//! Run with `cargo bench`.
+
+use rust_brainfuck::parser::Parser;
+use rust_brainfuck::scanner::Mode;
+use rust_brainfuck::token::Source;
+use rust_brainfuck::{arena, ast};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint::black_box;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// An allocator that keeps track of the bytes in use and their peak.
+struct Counting;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let in_use = IN_USE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(in_use, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const HELLO_WORLD: &str = ">++++++++[<+++++++++>-]<.>++++[<+++++++>-]<+.+++++++..+++.\
+>>++++++[<+++++++>-]<++.------------.>++++++[<+++++++++>-]<+.<.+++.------.--------.\
+>>>++++[<++++++++>-]<+.";
+
+// Runs f a few times and returns the fastest run, with the bytes its
+// result holds and the peak of memory in use while it ran.
+fn measure<T>(f: impl Fn() -> T) -> (Duration, usize, usize) {
+    let mut best = Duration::MAX;
+    let mut held = 0;
+    let mut peak = 0;
+    for _ in 0..5 {
+        let before = IN_USE.load(Ordering::Relaxed);
+        PEAK.store(before, Ordering::Relaxed);
+        let start = Instant::now();
+        let result = black_box(f());
+        best = best.min(start.elapsed());
+        held = IN_USE.load(Ordering::Relaxed) - before;
+        peak = PEAK.load(Ordering::Relaxed) - before;
+        drop(result);
+    }
+    (best, held, peak)
+}
+
+fn parse_ast(src: &[u8]) -> ast::Program {
+    let source = Rc::new(Source::new("", 1, src.len()));
+    Parser::new(source, src, Mode::default()).parse_program()
+}
+
+fn parse_arena(src: &[u8]) -> arena::Tree {
+    let source = Rc::new(Source::new("", 1, src.len()));
+    Parser::new(source, src, Mode::default()).parse_arena()
+}
+
+struct CountAst(Cell<usize>);
+
+impl ast::Visitor for CountAst {
+    fn visit(&self, _: &ast::Node) -> Option<&dyn ast::Visitor> {
+        self.0.set(self.0.get() + 1);
+        Some(self)
+    }
+}
+
+struct CountArena(Cell<usize>);
+
+impl arena::Visitor for CountArena {
+    fn visit(&self, _: &arena::Tree, _: arena::NodeId) -> Option<&dyn arena::Visitor> {
+        self.0.set(self.0.get() + 1);
+        Some(self)
+    }
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+fn report(name: &str, parse: (Duration, usize, usize), walk: Duration) {
+    let (time, held, peak) = parse;
+    println!(
+        "  {:<6} parse {:>9.2?}  tree {:>8.1} MiB  peak {:>8.1} MiB  walk {:>9.2?}",
+        name,
+        time,
+        mib(held),
+        mib(peak),
+        walk
+    );
+}
+
+fn bench(name: &str, src: &[u8]) {
+    println!("{} ({:.1} MiB of source)", name, mib(src.len()));
+
+    let parse = measure(|| parse_ast(src));
+    let program = ast::Node::Program(parse_ast(src));
+    let (walk, _, _) = measure(|| {
+        let v = CountAst(Cell::new(0));
+        ast::walk(&v, &program);
+        v.0.get()
+    });
+    drop(program);
+    report("ast", parse, walk);
+
+    let parse = measure(|| parse_arena(src));
+    let tree = parse_arena(src);
+    let (walk, _, _) = measure(|| {
+        let v = CountArena(Cell::new(0));
+        arena::walk(&v, &tree);
+        v.0.get()
+    });
+    report("arena", parse, walk);
+}
+
+fn main() {
+    bench("hello world x 20000", HELLO_WORLD.repeat(20_000).as_bytes());
+    bench("small loops", "+[->+<]>[-<+>]<".repeat(200_000).as_bytes());
+    let depth = 500_000;
+    bench(
+        "nested loops",
+        format!("{}+{}", "[".repeat(depth), "]".repeat(depth)).as_bytes(),
+    );
+}
@@ -0,0 +1,8 @@
+//! A compact representation of programs that keeps all nodes in one
+//! vector; see [`Tree`].
+
+mod tree;
+mod walk;
+
+pub use tree::*;
+pub use walk::*;
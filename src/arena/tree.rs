@@ -0,0 +1,376 @@
+use crate::ast::{self, Spanned};
+use crate::token::{self, Pos};
+use std::mem;
+
+/// The kind of a [`Node`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// `>`
+    IncPtr,
+    /// `<`
+    DecPtr,
+    /// `+`
+    IncByte,
+    /// `-`
+    DecByte,
+    /// `.`
+    OutputByte,
+    /// `,`
+    InputByte,
+    /// `#`
+    DebugDump,
+    /// A loop, whose body follows it.
+    Loop,
+    /// Source that could not be parsed.
+    BadNode,
+}
+
+/// The index of a node in its [`Tree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub usize);
+
+impl NodeId {
+    /// Returns the index of the node in [`Tree::nodes`].
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A node stored in a [`Tree`]. The body of a loop is stored right after
+/// the loop itself, so a node knows its children by their number alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    /// What the node is.
+    pub kind: Kind,
+    // number of nodes in the body of a loop, nested ones included
+    size: usize,
+    /// Position of the first character of the node.
+    pub pos: Pos,
+    // the rbrack of a loop, the end of a bad node
+    end: Pos,
+}
+
+impl Node {
+    /// Returns the position of the closing `]` of a loop, or NO_POS if the
+    /// node is not a loop or the loop is not closed.
+    pub fn rbrack(&self) -> Pos {
+        match self.kind {
+            Kind::Loop => self.end,
+            _ => token::NO_POS,
+        }
+    }
+
+    /// Returns the number of nodes in the body of a loop, including those
+    /// of nested loops.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// A program stored in a single vector of nodes in depth-first order. It
+/// holds the same information as an [`ast::Program`] at a fraction of the
+/// memory, without an allocation per loop.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    /// Position of the start of the file.
+    pub pos: Pos,
+    /// Position of the end of the program, as in [`ast::Program`].
+    pub eof: Pos,
+    /// Input data embedded after the `!` separator.
+    pub input: Vec<u8>,
+    /// Comments in source order; only collected when comments are scanned.
+    pub comments: Vec<ast::Comment>,
+}
+
+impl Tree {
+    /// Returns all nodes in depth-first order.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the node with the given id.
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.index()]
+    }
+
+    /// Returns the nodes of the program's body.
+    pub fn body(&self) -> Children<'_> {
+        Children {
+            nodes: &self.nodes,
+            next: 0,
+            end: self.nodes.len(),
+        }
+    }
+
+    /// Returns the nodes of the body of id, which are none unless it is a
+    /// loop.
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        let start = id.index() + 1;
+        Children {
+            nodes: &self.nodes,
+            next: start,
+            end: start + self.node(id).size(),
+        }
+    }
+
+    /// Returns the position just after id, like [`Spanned::end`].
+    pub fn end(&self, mut id: NodeId) -> Pos {
+        loop {
+            let node = self.node(id);
+            match node.kind {
+                Kind::BadNode => return node.end,
+                Kind::Loop if node.end.is_valid() => return node.end + 1usize,
+                // A loop without a ']' ends with its body.
+                Kind::Loop => match self.children(id).last() {
+                    Some(last) => id = last,
+                    None => return self.body_pos(id),
+                },
+                _ => return node.pos + 1usize,
+            }
+        }
+    }
+
+    // Returns the position of the body of a loop. The parser puts it at the
+    // token after the '[', which is the first node of the body, its ']' or
+    // else the end of the program.
+    fn body_pos(&self, id: NodeId) -> Pos {
+        let node = self.node(id);
+        if node.size > 0 {
+            self.nodes[id.index() + 1].pos
+        } else if node.end.is_valid() {
+            node.end
+        } else {
+            self.eof
+        }
+    }
+
+    /// Converts program into a tree. Positions of bodies that do not follow
+    /// from their contents the way the parser sets them are not kept.
+    pub fn from_ast(program: &ast::Program) -> Tree {
+        let mut builder = Builder::new();
+        ast::inspect(&program.body, |node, event| {
+            match (node, event) {
+                (ast::Node::Loop(l), ast::Event::Enter { .. }) => builder.open_loop(l.pos),
+                (ast::Node::Loop(l), ast::Event::Leave { .. }) => builder.close_loop(l.rbrack),
+                (ast::Node::BadNode(n), ast::Event::Enter { .. }) => builder.bad_node(n.from, n.to),
+                (_, ast::Event::Enter { .. }) => {
+                    let kind = match node {
+                        ast::Node::IncPtr(_) => Kind::IncPtr,
+                        ast::Node::DecPtr(_) => Kind::DecPtr,
+                        ast::Node::IncByte(_) => Kind::IncByte,
+                        ast::Node::DecByte(_) => Kind::DecByte,
+                        ast::Node::OutputByte(_) => Kind::OutputByte,
+                        ast::Node::InputByte(_) => Kind::InputByte,
+                        ast::Node::DebugDump(_) => Kind::DebugDump,
+                        // Bodies are implied by the node sizes.
+                        _ => return true,
+                    };
+                    builder.push(kind, node.pos());
+                }
+                _ => {}
+            }
+            true
+        });
+        builder.finish(
+            program.pos,
+            program.eof,
+            program.input.clone(),
+            program.comments.clone(),
+        )
+    }
+
+    /// Converts the tree into an [`ast::Program`] equal to the one the
+    /// parser builds for the same source.
+    pub fn to_ast(&self) -> ast::Program {
+        // the loops whose bodies are being built, with the nodes of the
+        // enclosing body that precede them
+        let mut open: Vec<(NodeId, Vec<ast::Node>)> = Vec::new();
+        let mut list = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let pos = node.pos;
+            match node.kind {
+                Kind::IncPtr => list.push(ast::Node::IncPtr(ast::IncPtr { pos })),
+                Kind::DecPtr => list.push(ast::Node::DecPtr(ast::DecPtr { pos })),
+                Kind::IncByte => list.push(ast::Node::IncByte(ast::IncByte { pos })),
+                Kind::DecByte => list.push(ast::Node::DecByte(ast::DecByte { pos })),
+                Kind::OutputByte => list.push(ast::Node::OutputByte(ast::OutputByte { pos })),
+                Kind::InputByte => list.push(ast::Node::InputByte(ast::InputByte { pos })),
+                Kind::DebugDump => list.push(ast::Node::DebugDump(ast::DebugDump { pos })),
+                Kind::BadNode => list.push(ast::Node::BadNode(ast::BadNode {
+                    from: pos,
+                    to: node.end,
+                })),
+                Kind::Loop => open.push((NodeId(i), mem::take(&mut list))),
+            }
+
+            // Close the loops that end with this node.
+            while let Some(&(id, _)) = open.last()
+                && id.index() + self.node(id).size() == i
+            {
+                let (id, outer) = open.pop().unwrap();
+                let body = mem::replace(&mut list, outer);
+                list.push(ast::Node::Loop(ast::Loop {
+                    pos: self.node(id).pos,
+                    body: Box::new(ast::Node::Body(ast::Body {
+                        pos: self.body_pos(id),
+                        list: body,
+                    })),
+                    rbrack: self.node(id).end,
+                }));
+            }
+        }
+
+        ast::Program {
+            pos: self.pos,
+            body: Box::new(ast::Node::Body(ast::Body {
+                pos: self.nodes.first().map_or(self.eof, |node| node.pos),
+                list,
+            })),
+            eof: self.eof,
+            input: self.input.clone(),
+            comments: self.comments.clone(),
+        }
+    }
+}
+
+/// Appends nodes to a [`Tree`] in source order.
+pub struct Builder {
+    nodes: Vec<Node>,
+    // indices of the loops that are not closed yet
+    open: Vec<usize>,
+}
+
+impl Builder {
+    /// Returns a builder of an empty tree.
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    /// Appends a node that has no body and is not a bad node.
+    pub fn push(&mut self, kind: Kind, pos: Pos) {
+        self.nodes.push(Node {
+            kind,
+            size: 0,
+            pos,
+            end: token::NO_POS,
+        });
+    }
+
+    /// Appends a bad node spanning from..to.
+    pub fn bad_node(&mut self, from: Pos, to: Pos) {
+        self.nodes.push(Node {
+            kind: Kind::BadNode,
+            size: 0,
+            pos: from,
+            end: to,
+        });
+    }
+
+    /// Starts a loop at pos. The nodes appended until the matching
+    /// close_loop make up its body.
+    pub fn open_loop(&mut self, pos: Pos) {
+        self.open.push(self.nodes.len());
+        self.push(Kind::Loop, pos);
+    }
+
+    /// Ends the innermost open loop at rbrack, which is NO_POS if the loop
+    /// has no `]`.
+    pub fn close_loop(&mut self, rbrack: Pos) {
+        let i = self.open.pop().expect("close_loop without an open loop");
+        let size = self.nodes.len() - i - 1;
+        let node = &mut self.nodes[i];
+        node.size = size;
+        node.end = rbrack;
+    }
+
+    /// Returns the tree built so far. Panics if a loop is still open.
+    pub fn finish(self, pos: Pos, eof: Pos, input: Vec<u8>, comments: Vec<ast::Comment>) -> Tree {
+        assert!(self.open.is_empty(), "finish with an open loop");
+        Tree {
+            nodes: self.nodes,
+            pos,
+            eof,
+            input,
+            comments,
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the nodes of a body, skipping the bodies of loops.
+#[derive(Clone)]
+pub struct Children<'a> {
+    nodes: &'a [Node],
+    next: usize,
+    end: usize,
+}
+
+impl Iterator for Children<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        if self.next >= self.end {
+            return None;
+        }
+        let id = NodeId(self.next);
+        self.next += 1 + self.nodes[self.next].size();
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Spanned;
+    use crate::parser::Parser;
+    use crate::scanner::Mode;
+    use crate::token::Source;
+    use std::rc::Rc;
+
+    fn parse(src: &[u8]) -> (Tree, ast::Program) {
+        let source = Rc::new(Source::new("", 1, src.len()));
+        let tree = Parser::new(source.clone(), src, Mode::default()).parse_arena();
+        let program = Parser::new(source, src, Mode::default()).parse_program();
+        (tree, program)
+    }
+
+    #[test]
+    fn tree() {
+        let (tree, program) = parse(b"++[>+[-]<-]");
+        assert_eq!(tree.nodes().len(), 9);
+        assert_eq!(tree.body().count(), 3);
+
+        let loop_id = tree.body().nth(2).unwrap();
+        assert_eq!(tree.node(loop_id).kind, Kind::Loop);
+        assert_eq!(tree.node(loop_id).size(), 6);
+        assert_eq!(tree.children(loop_id).count(), 5);
+        assert_eq!(tree.end(loop_id), Pos(12));
+        assert_eq!(tree.to_ast(), program);
+        assert_eq!(Tree::from_ast(&program).nodes(), tree.nodes());
+    }
+
+    #[test]
+    fn spans() {
+        // A bad node, a two-byte illegal character and unclosed loops.
+        for src in [&b"]+"[..], "é+".as_bytes(), b"+[[-]", b"[", b"[]"] {
+            let (tree, program) = parse(src);
+            let ast::Node::Body(body) = &*program.body else {
+                panic!("program without a body");
+            };
+            let ends: Vec<_> = body.list.iter().map(|node| node.end()).collect();
+            let tree_ends: Vec<_> = tree.body().map(|id| tree.end(id)).collect();
+            assert_eq!(tree_ends, ends, "{:?}", src);
+            let json = ast::Node::Program(program).to_json();
+            assert_eq!(ast::Node::Program(tree.to_ast()).to_json(), json);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::arena::tree::{NodeId, Tree};
+
+/// Like [`crate::ast::Visitor`], for the nodes of a [`Tree`].
+pub trait Visitor {
+    /// Visits the node id of tree; returns the visitor for its body, if any.
+    fn visit(&self, tree: &Tree, id: NodeId) -> Option<&dyn Visitor>;
+}
+
+/// Calls v for every node of tree in depth-first order. If a visit returns
+/// a visitor, it visits the body of the node; otherwise the body is
+/// skipped. Trees have no nodes for the program and loop bodies, so
+/// unlike ast::walk, the nodes of the program's body are visited first.
+pub fn walk(v: &dyn Visitor, tree: &Tree) {
+    // The nodes are stored in the order they are visited. The visitors
+    // of the bodies being visited are kept with the index their body ends
+    // at.
+    let mut stack: Vec<(&dyn Visitor, usize)> = Vec::new();
+    let nodes = tree.nodes();
+    let mut i = 0;
+    while i < nodes.len() {
+        while stack.last().is_some_and(|&(_, end)| end <= i) {
+            stack.pop();
+        }
+        let v = stack.last().map_or(v, |&(v, _)| v);
+        let end = i + 1 + nodes[i].size();
+        match v.visit(tree, NodeId(i)) {
+            Some(w) => {
+                if end > i + 1 {
+                    stack.push((w, end));
+                }
+                i += 1;
+            }
+            None => i = end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Kind;
+    use crate::parser::Parser;
+    use crate::scanner::Mode;
+    use crate::token::Source;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Records the kinds visited, skipping the bodies of loops at depth 1.
+    struct Kinds(RefCell<Vec<Kind>>, bool);
+
+    impl Visitor for Kinds {
+        fn visit(&self, tree: &Tree, id: NodeId) -> Option<&dyn Visitor> {
+            let kind = tree.node(id).kind;
+            self.0.borrow_mut().push(kind);
+            if kind == Kind::Loop && self.1 {
+                return None;
+            }
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn walk_tree() {
+        let src = b"+[-[>]]<[.]";
+        let source = Rc::new(Source::new("", 1, src.len()));
+        let tree = Parser::new(source, src, Mode::default()).parse_arena();
+
+        let all = Kinds(RefCell::default(), false);
+        walk(&all, &tree);
+        assert_eq!(all.0.borrow().len(), tree.nodes().len());
+
+        let top = Kinds(RefCell::default(), true);
+        walk(&top, &tree);
+        assert_eq!(
+            *top.0.borrow(),
+            [Kind::IncByte, Kind::Loop, Kind::DecPtr, Kind::Loop]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::ast::ast::Node;
+use crate::ast::walk::{attach_children, detach_children, placeholder};
+use std::mem;
+
+/// The node currently visited by [`apply`], together with the means to
+/// change it and the list it belongs to.
+pub struct Cursor<'a> {
+    node: &'a mut Node,
+    // nodes that precede the current one in its Body list, if it is in one
+    list: Option<&'a mut Vec<Node>>,
+    after: Vec<Node>,
+    deleted: bool,
+}
+
+impl Cursor<'_> {
+    /// Returns the current node.
+    pub fn node(&self) -> &Node {
+        self.node
+    }
+
+    /// Returns the current node for changing it in place.
+    pub fn node_mut(&mut self) -> &mut Node {
+        self.node
+    }
+
+    /// Reports the index of the current node in its Body list, counting
+    /// the nodes inserted and deleted before it. Returns None if the node
+    /// is not part of a list.
+    pub fn index(&self) -> Option<usize> {
+        self.list.as_ref().map(|list| list.len())
+    }
+
+    /// Replaces the current node with node and returns the old one. Called
+    /// from pre, the children of the new node are walked.
+    pub fn replace(&mut self, node: Node) -> Node {
+        mem::replace(self.node, node)
+    }
+
+    fn list(&mut self, op: &str) -> &mut Vec<Node> {
+        match &mut self.list {
+            Some(list) => list,
+            None => panic!("{} of a node that is not part of a Body list", op),
+        }
+    }
+
+    /// Deletes the current node from its list. Called from pre, its
+    /// children are not walked and post is not called for it. Panics if
+    /// the node is not part of a list.
+    pub fn delete(&mut self) {
+        self.list("delete");
+        self.deleted = true;
+    }
+
+    /// Inserts node before the current node in its list. The inserted node
+    /// is not walked. Panics if the current node is not part of a list.
+    pub fn insert_before(&mut self, node: Node) {
+        self.list("insert_before").push(node);
+    }
+
+    /// Inserts node directly after the current node in its list, so nodes
+    /// inserted later come first. The inserted node is not walked. Panics
+    /// if the current node is not part of a list.
+    pub fn insert_after(&mut self, node: Node) {
+        self.list("insert_after");
+        self.after.insert(0, node);
+    }
+}
+
+// A node whose children are being walked, moved out of it as in walk_mut.
+struct Frame {
+    node: Node,
+    // whether node is part of its parent's Body list
+    in_list: bool,
+    children: std::vec::IntoIter<Node>,
+    walked: Vec<Node>,
+    // nodes inserted after node so far
+    after: Vec<Node>,
+}
+
+// Puts a visited node back into its parent's list, or into root if it has
+// no parent.
+fn finish(stack: &mut [Frame], root: &mut Node, node: Node, deleted: bool, after: Vec<Node>) {
+    match stack.last_mut() {
+        Some(parent) => {
+            if !deleted {
+                parent.walked.push(node);
+            }
+            parent.walked.extend(after);
+        }
+        None => *root = node,
+    }
+}
+
+/// Walks root depth-first and calls pre for every node before its children
+/// and post after them. The callbacks can change the tree through the
+/// [`Cursor`]: nodes can be replaced anywhere, and deleted or surrounded
+/// with new nodes when they are part of a Body list.
+///
+/// If pre returns false, the children of the node are skipped and post is
+/// not called for it. If post returns false, the traversal stops. Pass
+/// `|_| true` for a callback that is not needed. Like walk, the traversal
+/// does not recurse.
+pub fn apply(
+    root: &mut Node,
+    mut pre: impl FnMut(&mut Cursor) -> bool,
+    mut post: impl FnMut(&mut Cursor) -> bool,
+) {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut next = (mem::replace(root, placeholder()), false);
+    loop {
+        let (mut node, in_list) = next;
+        let mut cursor = Cursor {
+            node: &mut node,
+            list: stack.last_mut().filter(|_| in_list).map(|f| &mut f.walked),
+            after: Vec::new(),
+            deleted: false,
+        };
+        let descend = pre(&mut cursor);
+        let (after, deleted) = (cursor.after, cursor.deleted);
+
+        if descend && !deleted {
+            let children = detach_children(&mut node);
+            stack.push(Frame {
+                node,
+                in_list,
+                children: children.into_iter(),
+                walked: Vec::new(),
+                after,
+            });
+        } else {
+            finish(&mut stack, root, node, deleted, after);
+            if stack.is_empty() {
+                return;
+            }
+        }
+
+        // Leave the nodes whose children are done, up to the next child
+        // still to be visited.
+        next = loop {
+            let frame = stack.last_mut().unwrap();
+            if let Some(child) = frame.children.next() {
+                break (child, matches!(frame.node, Node::Body(_)));
+            }
+
+            let mut frame = stack.pop().unwrap();
+            attach_children(&mut frame.node, frame.walked);
+            let mut cursor = Cursor {
+                node: &mut frame.node,
+                list: stack
+                    .last_mut()
+                    .filter(|_| frame.in_list)
+                    .map(|f| &mut f.walked),
+                after: frame.after,
+                deleted: false,
+            };
+            let go_on = post(&mut cursor);
+            let (after, deleted) = (cursor.after, cursor.deleted);
+            finish(&mut stack, root, frame.node, deleted, after);
+
+            if !go_on {
+                // Put the tree back together without visiting the rest.
+                while let Some(mut frame) = stack.pop() {
+                    frame.walked.extend(frame.children);
+                    attach_children(&mut frame.node, frame.walked);
+                    finish(&mut stack, root, frame.node, false, frame.after);
+                }
+                return;
+            }
+            if stack.is_empty() {
+                return;
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{DecPtr, IncPtr};
+    use crate::parser::parse_program_from;
+    use std::cell::RefCell;
+
+    fn parse(src: &str) -> Node {
+        parse_program_from(src).unwrap()
+    }
+
+    // Lists the commands of node in source order, ignoring positions.
+    fn commands(node: &mut Node) -> String {
+        let out = RefCell::new(String::new());
+        apply(
+            node,
+            |cursor| {
+                out.borrow_mut().push_str(match cursor.node() {
+                    Node::IncPtr(_) => ">",
+                    Node::DecPtr(_) => "<",
+                    Node::IncByte(_) => "+",
+                    Node::DecByte(_) => "-",
+                    Node::OutputByte(_) => ".",
+                    Node::InputByte(_) => ",",
+                    Node::Loop(_) => "[",
+                    _ => "",
+                });
+                true
+            },
+            |cursor| {
+                if let Node::Loop(_) = cursor.node() {
+                    out.borrow_mut().push(']');
+                }
+                true
+            },
+        );
+        out.into_inner()
+    }
+
+    #[test]
+    fn delete() {
+        let mut node = parse("-[+]-[[+]]");
+        apply(
+            &mut node,
+            |cursor| {
+                if let Node::Loop(_) = cursor.node() {
+                    cursor.delete();
+                    return false;
+                }
+                true
+            },
+            |_| true,
+        );
+        assert_eq!(commands(&mut node), "--");
+    }
+
+    #[test]
+    fn insert() {
+        let mut node = parse("+[-]");
+        let mut indices = Vec::new();
+        apply(
+            &mut node,
+            |cursor| {
+                if let Node::DecByte(n) = cursor.node() {
+                    let pos = n.pos;
+                    cursor.insert_before(Node::IncPtr(IncPtr { pos }));
+                    cursor.insert_after(Node::DecPtr(DecPtr { pos }));
+                    cursor.insert_after(Node::IncPtr(IncPtr { pos }));
+                }
+                indices.extend(cursor.index());
+                true
+            },
+            |cursor| {
+                if let Node::Loop(l) = cursor.node() {
+                    let pos = l.pos;
+                    cursor.insert_after(Node::OutputByte(crate::ast::OutputByte { pos }));
+                }
+                true
+            },
+        );
+        assert_eq!(commands(&mut node), "+[>-><].");
+        assert_eq!(indices, [0, 1, 1]);
+    }
+
+    #[test]
+    fn replace_and_stop() {
+        let mut node = parse("+[+]+");
+        let mut visited = 0;
+        apply(
+            &mut node,
+            |cursor| {
+                if let Node::IncByte(n) = cursor.node() {
+                    let pos = n.pos;
+                    cursor.replace(Node::DecPtr(DecPtr { pos }));
+                }
+                true
+            },
+            |cursor| {
+                visited += 1;
+                !matches!(cursor.node(), Node::Loop(_))
+            },
+        );
+        // The last + is neither visited nor lost.
+        assert_eq!(commands(&mut node), "<[<]+");
+        // The first two +, the loop body and the loop itself.
+        assert_eq!(visited, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "delete of a node that is not part of a Body list")]
+    fn delete_outside_list() {
+        let mut node = parse("+");
+        apply(
+            &mut node,
+            |cursor| {
+                cursor.delete();
+                true
+            },
+            |_| true,
+        );
+    }
+}
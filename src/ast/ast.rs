@@ -1,22 +1,41 @@
-use std::rc::Rc;
 use crate::token;
 
-trait Spanned {
+/// A node with a source range. Positions can be resolved with the
+/// `token::Source` or `token::FileSet` the node was parsed with.
+pub trait Spanned {
+    /// Returns the position of the first character of the node.
     fn pos(&self) -> token::Pos;
+    /// Returns the position of the first character immediately after the
+    /// node.
     fn end(&self) -> token::Pos;
 }
 
+/// A node of the syntax tree. Nodes compare and hash by structure: two
+/// trees are equal if they have the same shape and commands, whatever
+/// their positions and comments.
 #[derive(Debug)]
 pub enum Node {
+    /// The whole program.
     Program(Program),
+    /// `>`
     IncPtr(IncPtr),
+    /// `<`
     DecPtr(DecPtr),
+    /// `+`
     IncByte(IncByte),
+    /// `-`
     DecByte(DecByte),
+    /// `.`
     OutputByte(OutputByte),
+    /// `,`
     InputByte(InputByte),
+    /// `#`
+    DebugDump(DebugDump),
+    /// `[...]`
     Loop(Loop),
+    /// The nodes of a program or loop.
     Body(Body),
+    /// Source that could not be parsed.
     BadNode(BadNode),
 }
 
@@ -30,6 +49,7 @@ impl Spanned for Node {
             Node::DecByte(n) => n.pos(),
             Node::OutputByte(n) => n.pos(),
             Node::InputByte(n) => n.pos(),
+            Node::DebugDump(n) => n.pos(),
             Node::Loop(n) => n.pos(),
             Node::Body(n) => n.pos(),
             Node::BadNode(n) => n.pos(),
@@ -45,6 +65,7 @@ impl Spanned for Node {
             Node::DecByte(n) => n.end(),
             Node::OutputByte(n) => n.end(),
             Node::InputByte(n) => n.end(),
+            Node::DebugDump(n) => n.end(),
             Node::Loop(n) => n.end(),
             Node::Body(n) => n.end(),
             Node::BadNode(n) => n.end(),
@@ -53,9 +74,11 @@ impl Spanned for Node {
 }
 
 macro_rules! simple_node {
-    ($name:ident, $field:ident) => {
-        #[derive(Debug)]
+    ($name:ident, $ch:literal, $field:ident) => {
+        #[doc = concat!("The `", $ch, "` command.")]
+        #[derive(Debug, Clone)]
         pub struct $name {
+            /// Position of the command.
             pub $field: token::Pos,
         }
 
@@ -72,39 +95,78 @@ macro_rules! simple_node {
 }
 
 macro_rules! simple_nodes {
-    ([$($name:ident),*], $field:ident) => {
-        $(simple_node!($name, $field);)*
+    ([$($name:ident $ch:literal),*], $field:ident) => {
+        $(simple_node!($name, $ch, $field);)*
     };
 }
 
 simple_nodes!([
-    IncPtr,
-    DecPtr,
-    IncByte,
-    DecByte,
-    OutputByte,
-    InputByte
+    IncPtr ">",
+    DecPtr "<",
+    IncByte "+",
+    DecByte "-",
+    OutputByte ".",
+    InputByte ",",
+    DebugDump "#"
 ], pos);
 
-#[derive(Debug)]
+/// The root of a parsed program.
+#[derive(Debug, Clone)]
 pub struct Program {
-    pub body: Rc<Node>,
+    /// Position of the start of the file.
+    pub pos: token::Pos,
+    /// The program's [`Body`].
+    pub body: Box<Node>,
+    /// Position of the end of the program: the `!` separator if there is
+    /// one, otherwise the end of the file.
+    pub eof: token::Pos,
+    /// Input data embedded after the `!` separator.
+    pub input: Vec<u8>,
+    /// Comments in source order; only collected when comments are scanned.
+    pub comments: Vec<Comment>,
+}
+
+/// A run of non-command text on a single line.
+#[derive(Debug, Clone)]
+pub struct Comment {
+    /// Position of the first character of the comment.
+    pub pos: token::Pos,
+    /// Position just after the last character of the comment in the source,
+    /// which text may not tell: invalid UTF-8 in it is decoded as U+FFFD.
+    pub end: token::Pos,
+    /// The comment without its line break.
+    pub text: String,
 }
 
-impl Program {
-    pub fn pos(&self) -> token::Pos {
-        0.into()
+impl Spanned for Comment {
+    fn pos(&self) -> token::Pos {
+        self.pos
     }
 
-    pub fn end(&self) -> token::Pos {
-        0.into()
+    fn end(&self) -> token::Pos {
+        self.end
     }
 }
 
-#[derive(Debug)]
+impl Spanned for Program {
+    fn pos(&self) -> token::Pos {
+        self.pos
+    }
+
+    fn end(&self) -> token::Pos {
+        self.eof
+    }
+}
+
+/// A `[...]` loop.
+#[derive(Debug, Clone)]
 pub struct Loop {
+    /// Position of the opening `[`.
     pub pos: token::Pos,
-    pub body: Rc<Node>,
+    /// The loop's [`Body`].
+    pub body: Box<Node>,
+    /// Position of the closing `]`, or NO_POS if the loop is not closed.
+    pub rbrack: token::Pos,
 }
 
 impl Spanned for Loop {
@@ -113,20 +175,43 @@ impl Spanned for Loop {
     }
 
     fn end(&self) -> token::Pos {
-        if let Node::Body(body) = self.body.as_ref() {
-            body.end()
+        if self.rbrack.is_valid() {
+            self.rbrack + 1usize
         } else {
-            self.pos + 1usize
+            self.body.end()
         }
     }
 }
 
-#[derive(Debug)]
+/// The nodes of a program or loop. An empty body has an empty span at the
+/// token that ends it.
+///
+/// Dropping a `Body` tears nested loops down iteratively, so it implements
+/// `Drop` and cannot be destructured by value: take the nodes out with
+/// `std::mem::take(&mut body.list)` instead.
+#[derive(Debug, Clone)]
 pub struct Body {
+    /// Position of the first token of the body.
     pub pos: token::Pos,
+    /// The nodes in source order.
     pub list: Vec<Node>,
 }
 
+impl Drop for Body {
+    fn drop(&mut self) {
+        // Tear nested loops down iteratively; the default recursive drop
+        // would overflow the stack on deeply nested programs.
+        let mut stack = std::mem::take(&mut self.list);
+        while let Some(node) = stack.pop() {
+            if let Node::Loop(l) = node
+                && let Node::Body(mut body) = *l.body
+            {
+                stack.append(&mut body.list);
+            }
+        }
+    }
+}
+
 impl Spanned for Body {
     fn pos(&self) -> token::Pos {
         self.pos
@@ -136,14 +221,17 @@ impl Spanned for Body {
         if let Some(last) = self.list.last() {
             last.end()
         } else {
-            self.pos + 1usize
+            self.pos
         }
     }
 }
 
-#[derive(Debug)]
+/// Source that could not be parsed.
+#[derive(Debug, Clone)]
 pub struct BadNode {
+    /// Position of the first character.
     pub from: token::Pos,
+    /// Position just after the last character.
     pub to: token::Pos,
 }
 
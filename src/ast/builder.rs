@@ -0,0 +1,221 @@
+use crate::ast::ast::*;
+use crate::token::Pos;
+
+/// Builds a program in code. Nodes get the positions they would have if
+/// the program were written out without whitespace, starting at Pos 1, so
+/// a built program equals the parsed source even with positions:
+///
+/// ```
+/// use rust_brainfuck::ast::{Node, Program};
+/// use rust_brainfuck::parser::parse_program_from;
+///
+/// let program = Program::builder()
+///     .add(5)
+///     .loop_(|b| b.sub(1).right(1).add(1).left(1))
+///     .build();
+/// let parsed = parse_program_from("+++++[->+<]").unwrap();
+/// assert_eq!(Node::from(program).to_json(), parsed.to_json());
+/// ```
+pub struct Builder {
+    // position of the next command
+    next: Pos,
+    list: Vec<Node>,
+}
+
+impl Program {
+    /// Returns a builder for a program, starting at position 1.
+    pub fn builder() -> Builder {
+        Builder {
+            next: Pos(1),
+            list: Vec::new(),
+        }
+    }
+}
+
+impl Builder {
+    fn commands(mut self, n: usize, node: fn(Pos) -> Node) -> Self {
+        for _ in 0..n {
+            self.list.push(node(self.next));
+            self.next = self.next + 1usize;
+        }
+        self
+    }
+
+    /// Appends n `+` commands.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, n: usize) -> Self {
+        self.commands(n, |pos| Node::IncByte(IncByte { pos }))
+    }
+
+    /// Appends n `-` commands.
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, n: usize) -> Self {
+        self.commands(n, |pos| Node::DecByte(DecByte { pos }))
+    }
+
+    /// Appends n `>` commands.
+    pub fn right(self, n: usize) -> Self {
+        self.commands(n, |pos| Node::IncPtr(IncPtr { pos }))
+    }
+
+    /// Appends n `<` commands.
+    pub fn left(self, n: usize) -> Self {
+        self.commands(n, |pos| Node::DecPtr(DecPtr { pos }))
+    }
+
+    /// Appends a `.` command.
+    pub fn output(self) -> Self {
+        self.commands(1, |pos| Node::OutputByte(OutputByte { pos }))
+    }
+
+    /// Appends a `,` command.
+    pub fn input(self) -> Self {
+        self.commands(1, |pos| Node::InputByte(InputByte { pos }))
+    }
+
+    /// Appends a `#` command.
+    pub fn dump(self) -> Self {
+        self.commands(1, |pos| Node::DebugDump(DebugDump { pos }))
+    }
+
+    /// Appends a loop whose body f appends to the builder it is passed.
+    pub fn loop_(mut self, f: impl FnOnce(Builder) -> Builder) -> Self {
+        let pos = self.next;
+        let body = f(Builder {
+            next: pos + 1usize,
+            list: Vec::new(),
+        });
+        let rbrack = body.next;
+        self.list.push(Node::Loop(Loop {
+            pos,
+            body: Box::new(Node::Body(body.body())),
+            rbrack,
+        }));
+        self.next = rbrack + 1usize;
+        self
+    }
+
+    // Like the parser, places a body at its first token, which is the
+    // token that ends it if it is empty.
+    fn body(self) -> Body {
+        Body {
+            pos: self.list.first().map_or(self.next, Spanned::pos),
+            list: self.list,
+        }
+    }
+
+    /// Returns the program built so far.
+    pub fn build(self) -> Program {
+        let eof = self.next;
+        Program {
+            pos: Pos(1),
+            body: Box::new(Node::Body(self.body())),
+            eof,
+            input: Vec::new(),
+            comments: Vec::new(),
+        }
+    }
+}
+
+impl From<Program> for Node {
+    fn from(program: Program) -> Self {
+        Node::Program(program)
+    }
+}
+
+/// Builds an [`ast::Node`](crate::ast::Node) holding the program written
+/// as the macro's arguments, with positions as described at [`Builder`]:
+///
+/// ```
+/// use rust_brainfuck::bf;
+/// use rust_brainfuck::parser::parse_program_from;
+///
+/// let node = bf!(++++[->++<]>.);
+/// assert_eq!(node.to_json(), parse_program_from("++++[->++<]>.").unwrap().to_json());
+/// ```
+///
+/// Rust reads some pairs of commands as one token, such as `->`, `<<` or
+/// `..`; they are understood as the commands they are made of. Brackets
+/// must be balanced.
+#[macro_export]
+macro_rules! bf {
+    ($($t:tt)*) => {{
+        let b = $crate::ast::Program::builder();
+        $(let b = $crate::bf_token!(b, $t);)*
+        $crate::ast::Node::from(b.build())
+    }};
+}
+
+/// Appends the commands of a single token tree of [`bf!`] to builder b.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! bf_token {
+    ($b:ident, +) => { $b.add(1) };
+    ($b:ident, -) => { $b.sub(1) };
+    ($b:ident, >) => { $b.right(1) };
+    ($b:ident, <) => { $b.left(1) };
+    ($b:ident, .) => { $b.output() };
+    ($b:ident, ,) => { $b.input() };
+    ($b:ident, #) => { $b.dump() };
+    ($b:ident, >>) => { $b.right(2) };
+    ($b:ident, <<) => { $b.left(2) };
+    ($b:ident, ->) => { $b.sub(1).right(1) };
+    ($b:ident, <-) => { $b.left(1).sub(1) };
+    ($b:ident, ..) => { $b.output().output() };
+    ($b:ident, ...) => { $b.output().output().output() };
+    ($b:ident, [$($t:tt)*]) => {
+        $b.loop_(|b| {
+            $(let b = $crate::bf_token!(b, $t);)*
+            b
+        })
+    };
+    ($b:ident, $t:tt) => {
+        compile_error!(concat!("not a Brainfuck command: ", stringify!($t)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program_from;
+
+    // Checks that node is the tree parsed from src, positions included.
+    fn check(node: Node, src: &str) {
+        let parsed = parse_program_from(src).unwrap();
+        assert_eq!(node.to_json(), parsed.to_json(), "{}", src);
+    }
+
+    #[test]
+    fn build() {
+        let program = Program::builder()
+            .add(5)
+            .loop_(|b| b.sub(1).right(1).add(1).left(1))
+            .build();
+        check(program.into(), "+++++[->+<]");
+
+        let program = Program::builder()
+            .add(3)
+            .loop_(|b| b.sub(1).right(1).add(2).left(1))
+            .right(1)
+            .output()
+            .input()
+            .build();
+        check(program.into(), "+++[->++<]>.,");
+
+        check(Program::builder().loop_(|b| b).build().into(), "[]");
+        check(
+            Program::builder().loop_(|b| b.loop_(|b| b)).build().into(),
+            "[[]]",
+        );
+        check(Program::builder().build().into(), "");
+    }
+
+    #[test]
+    fn bf_macro() {
+        check(crate::bf!(++[->+<]), "++[->+<]");
+        check(crate::bf!(,[.,]), ",[.,]");
+        check(crate::bf!(>><<..<-...), ">><<..<-...");
+        check(crate::bf!([[]+]), "[[]+]");
+        check(crate::bf!(), "");
+    }
+}
@@ -0,0 +1,459 @@
+use crate::ast::ast::*;
+use crate::ast::walk::{Event, inspect};
+use crate::json::{self, Reader};
+use crate::token::{NO_POS, Pos};
+use std::fmt::{self, Write};
+
+/// The error returned when a tree cannot be decoded from JSON.
+pub use crate::json::Error as JsonError;
+
+/// A Body whose list is being written, at the depth of the Body.
+struct OpenList {
+    depth: usize,
+    first: bool,
+}
+
+fn write_simple(w: &mut dyn Write, name: &str, pos: Pos) -> fmt::Result {
+    write!(w, "{{\"type\":\"{}\",\"pos\":{}}}", name, pos.0)
+}
+
+fn write_enter(w: &mut dyn Write, node: &Node) -> fmt::Result {
+    match node {
+        Node::Program(n) => {
+            write!(
+                w,
+                "{{\"type\":\"Program\",\"pos\":{},\"eof\":{},\"input\":[",
+                n.pos.0, n.eof.0
+            )?;
+            for (i, b) in n.input.iter().enumerate() {
+                if i > 0 {
+                    w.write_char(',')?;
+                }
+                write!(w, "{}", b)?;
+            }
+            w.write_str("],\"comments\":[")?;
+            for (i, comment) in n.comments.iter().enumerate() {
+                if i > 0 {
+                    w.write_char(',')?;
+                }
+                write!(
+                    w,
+                    "{{\"pos\":{},\"end\":{},\"text\":",
+                    comment.pos.0, comment.end.0
+                )?;
+                json::write_string(w, &comment.text)?;
+                w.write_char('}')?;
+            }
+            w.write_str("],\"body\":")
+        }
+        Node::Loop(n) => write!(
+            w,
+            "{{\"type\":\"Loop\",\"pos\":{},\"rbrack\":{},\"body\":",
+            n.pos.0, n.rbrack.0
+        ),
+        Node::Body(n) => write!(w, "{{\"type\":\"Body\",\"pos\":{},\"list\":[", n.pos.0),
+        Node::IncPtr(n) => write_simple(w, "IncPtr", n.pos),
+        Node::DecPtr(n) => write_simple(w, "DecPtr", n.pos),
+        Node::IncByte(n) => write_simple(w, "IncByte", n.pos),
+        Node::DecByte(n) => write_simple(w, "DecByte", n.pos),
+        Node::OutputByte(n) => write_simple(w, "OutputByte", n.pos),
+        Node::InputByte(n) => write_simple(w, "InputByte", n.pos),
+        Node::DebugDump(n) => write_simple(w, "DebugDump", n.pos),
+        Node::BadNode(n) => write!(
+            w,
+            "{{\"type\":\"BadNode\",\"from\":{},\"to\":{}}}",
+            n.from.0, n.to.0
+        ),
+    }
+}
+
+impl Node {
+    /// Writes the tree as JSON. Every node is an object with a "type"
+    /// member naming its variant and a member for each of its fields;
+    /// positions are written as Pos values:
+    ///
+    /// ```text
+    /// {"type":"Loop","pos":3,"rbrack":5,"body":{"type":"Body","pos":4,"list":[{"type":"DecByte","pos":4}]}}
+    /// ```
+    ///
+    /// The input of a Program is an array of bytes.
+    pub fn write_json(&self, w: &mut dyn Write) -> fmt::Result {
+        let mut result = Ok(());
+        let mut lists: Vec<OpenList> = Vec::new();
+        inspect(self, |node, event| {
+            if result.is_err() {
+                return false;
+            }
+            result = match event {
+                Event::Enter { depth } => {
+                    let mut separate = Ok(());
+                    if let Some(list) = lists.last_mut().filter(|l| l.depth + 1 == depth) {
+                        if !list.first {
+                            separate = w.write_char(',');
+                        }
+                        list.first = false;
+                    }
+                    if let Node::Body(_) = node {
+                        lists.push(OpenList { depth, first: true });
+                    }
+                    separate.and_then(|_| write_enter(w, node))
+                }
+                Event::Leave { .. } => match node {
+                    Node::Body(_) => {
+                        lists.pop();
+                        w.write_str("]}")
+                    }
+                    Node::Program(_) | Node::Loop(_) => w.write_char('}'),
+                    _ => Ok(()),
+                },
+            };
+            true
+        });
+        result
+    }
+
+    /// Returns the tree encoded as by write_json.
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+        self.write_json(&mut s).unwrap();
+        s
+    }
+
+    /// Decodes a tree written by write_json. Members may come in any order
+    /// and unknown members are ignored.
+    pub fn from_json(src: &str) -> Result<Node, JsonError> {
+        Decoder {
+            reader: Reader::new(src),
+            stack: Vec::new(),
+        }
+        .decode()
+    }
+}
+
+/// The members of an object decoded so far.
+#[derive(Default)]
+struct Fields {
+    kind: Option<String>,
+    pos: Option<Pos>,
+    eof: Option<Pos>,
+    rbrack: Option<Pos>,
+    from: Option<Pos>,
+    to: Option<Pos>,
+    end: Option<Pos>,
+    text: Option<String>,
+    input: Option<Vec<u8>>,
+    comments: Option<Vec<Comment>>,
+    body: Option<Node>,
+    list: Option<Vec<Node>>,
+}
+
+enum Frame {
+    // a node, or a comment if comment is set
+    Object { fields: Box<Fields>, comment: bool },
+    // an array member of the object below
+    Array(ArrayKind),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArrayKind {
+    Input,
+    Comments,
+    List,
+}
+
+struct Decoder<'a> {
+    reader: Reader<'a>,
+    stack: Vec<Frame>,
+}
+
+impl Decoder<'_> {
+    fn next(&mut self) -> Result<json::Event, json::Error> {
+        self.reader
+            .next()?
+            .ok_or_else(|| self.reader.error("unexpected end of the document"))
+    }
+
+    fn fields(&mut self) -> &mut Fields {
+        match self.stack.last_mut() {
+            Some(Frame::Object { fields, .. }) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    fn pos(&self, event: json::Event) -> Result<Pos, json::Error> {
+        match event {
+            json::Event::Number(n) => n
+                .parse()
+                .map(Pos)
+                .map_err(|_| self.reader.error("expected a position")),
+            _ => Err(self.reader.error("expected a position")),
+        }
+    }
+
+    fn decode(mut self) -> Result<Node, json::Error> {
+        let mut event = self.next()?;
+        loop {
+            match (self.stack.last(), event) {
+                (None, json::Event::ObjectStart) => {
+                    self.stack.push(Frame::Object {
+                        fields: Box::default(),
+                        comment: false,
+                    });
+                }
+                (Some(Frame::Array(kind)), json::Event::ObjectStart)
+                    if *kind != ArrayKind::Input =>
+                {
+                    let comment = *kind == ArrayKind::Comments;
+                    self.stack.push(Frame::Object {
+                        fields: Box::default(),
+                        comment,
+                    });
+                }
+                (Some(Frame::Array(ArrayKind::Input)), json::Event::Number(n)) => {
+                    let b = n
+                        .parse()
+                        .map_err(|_| self.reader.error("expected a byte"))?;
+                    self.input_fields().input.get_or_insert_default().push(b);
+                }
+                (Some(Frame::Array(_)), json::Event::ArrayEnd) => {
+                    self.stack.pop();
+                }
+                (Some(Frame::Object { .. }), json::Event::Key(key)) => {
+                    self.member(&key)?;
+                }
+                (Some(Frame::Object { .. }), json::Event::ObjectEnd) => {
+                    let Some(Frame::Object { fields, comment }) = self.stack.pop() else {
+                        unreachable!();
+                    };
+                    let fields = *fields;
+                    let offset = self.reader.offset();
+                    let at = |msg: String| json::Error { offset, msg };
+                    if comment {
+                        let comment = Comment {
+                            pos: fields
+                                .pos
+                                .ok_or_else(|| at("missing member \"pos\" in comment".into()))?,
+                            end: fields
+                                .end
+                                .ok_or_else(|| at("missing member \"end\" in comment".into()))?,
+                            text: fields
+                                .text
+                                .ok_or_else(|| at("missing member \"text\" in comment".into()))?,
+                        };
+                        self.input_fields()
+                            .comments
+                            .get_or_insert_default()
+                            .push(comment);
+                    } else {
+                        let node = build(fields).map_err(at)?;
+                        match self.stack.last_mut() {
+                            None => {
+                                // Fails if anything follows the document.
+                                self.reader.next()?;
+                                return Ok(node);
+                            }
+                            Some(Frame::Array(_)) => {
+                                self.input_fields().list.get_or_insert_default().push(node);
+                            }
+                            Some(Frame::Object { fields, .. }) => fields.body = Some(node),
+                        }
+                    }
+                }
+                (None, _) => return Err(self.reader.error("expected an object")),
+                (Some(_), _) => return Err(self.reader.error("unexpected value")),
+            }
+            event = self.next()?;
+        }
+    }
+
+    // Returns the fields of the object that owns the array on top of the
+    // stack.
+    fn input_fields(&mut self) -> &mut Fields {
+        match self.stack.iter_mut().rev().nth(1) {
+            Some(Frame::Object { fields, .. }) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    // Reads the value of the member key of the object on top of the stack.
+    // Values that are objects or arrays are left for decode.
+    fn member(&mut self, key: &str) -> Result<(), json::Error> {
+        let event = self.next()?;
+        match key {
+            "type" => match event {
+                json::Event::String(s) => self.fields().kind = Some(s),
+                _ => return Err(self.reader.error("expected a string")),
+            },
+            "pos" => self.fields().pos = Some(self.pos(event)?),
+            "eof" => self.fields().eof = Some(self.pos(event)?),
+            "rbrack" => self.fields().rbrack = Some(self.pos(event)?),
+            "from" => self.fields().from = Some(self.pos(event)?),
+            "to" => self.fields().to = Some(self.pos(event)?),
+            "end" => self.fields().end = Some(self.pos(event)?),
+            "text" => match event {
+                json::Event::String(s) => self.fields().text = Some(s),
+                _ => return Err(self.reader.error("expected a string")),
+            },
+            "body" if event == json::Event::ObjectStart => {
+                self.stack.push(Frame::Object {
+                    fields: Box::default(),
+                    comment: false,
+                });
+            }
+            "input" | "comments" | "list" if event == json::Event::ArrayStart => {
+                let kind = match key {
+                    "input" => ArrayKind::Input,
+                    "comments" => ArrayKind::Comments,
+                    _ => ArrayKind::List,
+                };
+                let fields = self.fields();
+                match kind {
+                    ArrayKind::Input => fields.input = Some(Vec::new()),
+                    ArrayKind::Comments => fields.comments = Some(Vec::new()),
+                    ArrayKind::List => fields.list = Some(Vec::new()),
+                }
+                self.stack.push(Frame::Array(kind));
+            }
+            "body" | "input" | "comments" | "list" => {
+                return Err(self.reader.error(format!("invalid value for \"{}\"", key)));
+            }
+            _ => self.reader.skip(&event)?,
+        }
+        Ok(())
+    }
+}
+
+// Builds the node described by fields.
+fn build(fields: Fields) -> Result<Node, String> {
+    let kind = fields.kind.ok_or("missing member \"type\"")?;
+    let need = |pos: Option<Pos>, name: &str| {
+        pos.ok_or_else(|| format!("missing member \"{}\" in {}", name, kind))
+    };
+
+    let node = match kind.as_str() {
+        "Program" => Node::Program(Program {
+            pos: need(fields.pos, "pos")?,
+            body: Box::new(
+                fields
+                    .body
+                    .ok_or_else(|| format!("missing member \"body\" in {}", kind))?,
+            ),
+            eof: need(fields.eof, "eof")?,
+            input: fields.input.unwrap_or_default(),
+            comments: fields.comments.unwrap_or_default(),
+        }),
+        "Loop" => Node::Loop(Loop {
+            pos: need(fields.pos, "pos")?,
+            body: Box::new(
+                fields
+                    .body
+                    .ok_or_else(|| format!("missing member \"body\" in {}", kind))?,
+            ),
+            rbrack: fields.rbrack.unwrap_or(NO_POS),
+        }),
+        "Body" => Node::Body(Body {
+            pos: need(fields.pos, "pos")?,
+            list: fields.list.unwrap_or_default(),
+        }),
+        "IncPtr" => Node::IncPtr(IncPtr {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "DecPtr" => Node::DecPtr(DecPtr {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "IncByte" => Node::IncByte(IncByte {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "DecByte" => Node::DecByte(DecByte {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "OutputByte" => Node::OutputByte(OutputByte {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "InputByte" => Node::InputByte(InputByte {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "DebugDump" => Node::DebugDump(DebugDump {
+            pos: need(fields.pos, "pos")?,
+        }),
+        "BadNode" => Node::BadNode(BadNode {
+            from: need(fields.from, "from")?,
+            to: need(fields.to, "to")?,
+        }),
+        _ => return Err(format!("unknown node type {:?}", kind)),
+    };
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Mode;
+    use crate::token::Source;
+    use std::rc::Rc;
+
+    #[test]
+    fn round_trip() {
+        let mode = Mode::SCAN_COMMENTS | Mode::INPUT_SEPARATOR;
+        let cases: [&[u8]; 6] = [
+            b"+[-]>.",
+            b"",
+            // A comment with invalid UTF-8 and the input data.
+            b"+ a\xffb \"q\"\n.!data",
+            // Unclosed loops have no rbrack.
+            b"+[[-]",
+            // Bad nodes.
+            b"]+",
+            "\u{1}é+".as_bytes(),
+        ];
+        for src in cases {
+            // Trees with errors can be encoded too.
+            let source = Rc::new(Source::new("", 1, src.len()));
+            let mut parser = Parser::new(source, src, mode);
+            let node = Node::Program(parser.parse_program());
+            let json = node.to_json();
+            let decoded = Node::from_json(&json).unwrap();
+            assert_eq!(decoded.to_json(), json, "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn documents() {
+        let node = Node::from_json(
+            r#"{"list":[{"pos":1,"type":"IncByte","x":[{}]}],"type":"Body","pos":1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            node.to_json(),
+            r#"{"type":"Body","pos":1,"list":[{"type":"IncByte","pos":1}]}"#
+        );
+
+        let loop_json = r#"{"type":"Loop","pos":3,"rbrack":5,"body":{"type":"Body","pos":4,"list":[{"type":"DecByte","pos":4}]}}"#;
+        assert_eq!(Node::from_json(loop_json).unwrap().to_json(), loop_json);
+
+        let error = |src| Node::from_json(src).unwrap_err().to_string();
+        assert_eq!(error("{"), "offset 1: expected a string key");
+        assert_eq!(error("[]"), "offset 1: expected an object");
+        assert_eq!(
+            error(r#"{"type":"IncByte","pos":1}{}"#),
+            "offset 26: unexpected data after the document"
+        );
+        assert_eq!(
+            error(r#"{"type":"BadNode","from":1}"#),
+            "offset 27: missing member \"to\" in BadNode"
+        );
+        assert_eq!(
+            error(r#"{"type":"Loop","pos":1}"#),
+            "offset 23: missing member \"body\" in Loop"
+        );
+        assert_eq!(
+            error(r#"{"type":"Nop","pos":1}"#),
+            "offset 22: unknown node type \"Nop\""
+        );
+        assert_eq!(
+            error(r#"{"type":"IncByte","pos":"1"}"#),
+            "offset 27: expected a position"
+        );
+    }
+}
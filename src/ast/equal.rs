@@ -0,0 +1,259 @@
+use crate::ast::ast::*;
+use crate::ast::walk::{Event, attach_children, inspect, placeholder};
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+// Like the derived implementations, but without recursion, so deeply
+// nested trees can be cloned.
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        // copies of the nodes entered, and their children copied so far
+        let mut stack: Vec<(Node, Vec<Node>)> = Vec::new();
+        let mut root = None;
+        inspect(self, |node, event| {
+            let copy = match (node, event) {
+                (Node::Program(n), Event::Enter { .. }) => {
+                    let shell = Program {
+                        pos: n.pos,
+                        body: Box::new(placeholder()),
+                        eof: n.eof,
+                        input: n.input.clone(),
+                        comments: n.comments.clone(),
+                    };
+                    stack.push((Node::Program(shell), Vec::new()));
+                    return true;
+                }
+                (Node::Loop(n), Event::Enter { .. }) => {
+                    let shell = Loop {
+                        pos: n.pos,
+                        body: Box::new(placeholder()),
+                        rbrack: n.rbrack,
+                    };
+                    stack.push((Node::Loop(shell), Vec::new()));
+                    return true;
+                }
+                (Node::Body(n), Event::Enter { .. }) => {
+                    let shell = Body {
+                        pos: n.pos,
+                        list: Vec::new(),
+                    };
+                    stack.push((Node::Body(shell), Vec::with_capacity(n.list.len())));
+                    return true;
+                }
+                (Node::Program(_) | Node::Loop(_) | Node::Body(_), Event::Leave { .. }) => {
+                    let (mut copy, children) = stack.pop().unwrap();
+                    attach_children(&mut copy, children);
+                    copy
+                }
+                (_, Event::Leave { .. }) => return true,
+                (Node::IncPtr(n), _) => Node::IncPtr(n.clone()),
+                (Node::DecPtr(n), _) => Node::DecPtr(n.clone()),
+                (Node::IncByte(n), _) => Node::IncByte(n.clone()),
+                (Node::DecByte(n), _) => Node::DecByte(n.clone()),
+                (Node::OutputByte(n), _) => Node::OutputByte(n.clone()),
+                (Node::InputByte(n), _) => Node::InputByte(n.clone()),
+                (Node::DebugDump(n), _) => Node::DebugDump(n.clone()),
+                (Node::BadNode(n), _) => Node::BadNode(n.clone()),
+            };
+            match stack.last_mut() {
+                Some((_, children)) => children.push(copy),
+                None => root = Some(copy),
+            }
+            true
+        });
+        root.unwrap()
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Node::Program(a), Node::Program(b)) => {
+                    if a.input != b.input {
+                        return false;
+                    }
+                    stack.push((&a.body, &b.body));
+                }
+                (Node::Loop(a), Node::Loop(b)) => stack.push((&a.body, &b.body)),
+                (Node::Body(a), Node::Body(b)) => {
+                    if a.list.len() != b.list.len() {
+                        return false;
+                    }
+                    stack.extend(a.list.iter().zip(&b.list));
+                }
+                (a, b) => {
+                    if mem::discriminant(a) != mem::discriminant(b) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Eq for Node {}
+
+/// Compares the input and the body; positions and comments are ignored.
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.input == other.input && self.body == other.body
+    }
+}
+
+impl Eq for Program {}
+
+impl PartialEq for Loop {
+    fn eq(&self, other: &Self) -> bool {
+        self.body == other.body
+    }
+}
+
+impl Eq for Loop {}
+
+impl PartialEq for Body {
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl Eq for Body {}
+
+// Nodes without children are all alike apart from their positions.
+macro_rules! equal_nodes {
+    ($($name:ident),*) => {
+        $(
+            impl PartialEq for $name {
+                fn eq(&self, _: &Self) -> bool {
+                    true
+                }
+            }
+
+            impl Eq for $name {}
+        )*
+    };
+}
+
+equal_nodes!(
+    IncPtr, DecPtr, IncByte, DecByte, OutputByte, InputByte, DebugDump, BadNode
+);
+
+// Marks the end of the children of a node in the hashed byte stream.
+const END: u8 = 0xFF;
+
+fn tag(node: &Node) -> u8 {
+    match node {
+        Node::Program(_) => 0,
+        Node::IncPtr(_) => 1,
+        Node::DecPtr(_) => 2,
+        Node::IncByte(_) => 3,
+        Node::DecByte(_) => 4,
+        Node::OutputByte(_) => 5,
+        Node::InputByte(_) => 6,
+        Node::DebugDump(_) => 7,
+        Node::Loop(_) => 8,
+        Node::Body(_) => 9,
+        Node::BadNode(_) => 10,
+    }
+}
+
+/// Feeds the structure of the tree to the hasher as a byte stream that is
+/// the same on every platform: a tag for every node in depth-first order,
+/// with the children of a node followed by an end marker. Equal trees hash
+/// alike.
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        inspect(self, |node, event| {
+            match (node, event) {
+                (Node::Program(n), Event::Enter { .. }) => {
+                    state.write(&[tag(node)]);
+                    state.write(&(n.input.len() as u64).to_le_bytes());
+                    state.write(&n.input);
+                }
+                (_, Event::Enter { .. }) => state.write(&[tag(node)]),
+                (Node::Program(_) | Node::Loop(_) | Node::Body(_), Event::Leave { .. }) => {
+                    state.write(&[END])
+                }
+                _ => {}
+            }
+            true
+        });
+    }
+}
+
+// The 64-bit FNV-1a hash.
+struct Fnv64(u64);
+
+impl Hasher for Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Node {
+    /// Returns a hash of the structure of the tree that does not change
+    /// between runs, builds or platforms, so it can be stored to find
+    /// programs again. Like equality, it ignores positions and comments.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = Fnv64(0xcbf29ce484222325);
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program_from, parse_program_with_mode};
+    use crate::scanner::Mode;
+
+    fn parse(src: &str) -> Node {
+        parse_program_from(src).unwrap()
+    }
+
+    #[test]
+    fn equality_and_hash() {
+        let a = parse("+[->+<]");
+        let b = parse("  +  [ - > + < ]  ");
+        assert_eq!(a, b);
+        assert_eq!(a.stable_hash(), b.stable_hash());
+        assert_eq!(a.clone(), a);
+        assert_eq!(a.clone().to_json(), a.to_json());
+
+        let c = parse("+[->-<]");
+        assert_ne!(a, c);
+        assert_ne!(a.stable_hash(), c.stable_hash());
+        // Moving a command out of a loop changes the structure.
+        assert_ne!(parse("[-]+"), parse("[-+]"));
+        assert_ne!(parse("[-]+").stable_hash(), parse("[-+]").stable_hash());
+
+        // Comments are ignored, the input is not.
+        let mode = Mode::SCAN_COMMENTS | Mode::INPUT_SEPARATOR;
+        let with_comment = parse_program_with_mode("+ a !x", mode).unwrap();
+        assert_eq!(with_comment, parse_program_with_mode("+!x", mode).unwrap());
+        assert_ne!(with_comment, parse_program_with_mode("+!y", mode).unwrap());
+
+        // The hash must not change between releases.
+        assert_eq!(parse("+[-]").stable_hash(), 0x0f94_41c1_b741_0bb0);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let src = "[".repeat(depth) + &"]".repeat(depth);
+        let node = parse(&src);
+        let copy = node.clone();
+        assert_eq!(node, copy);
+        assert_eq!(node.stable_hash(), copy.stable_hash());
+        assert_eq!(Node::from_json(&node.to_json()).unwrap(), node);
+    }
+}
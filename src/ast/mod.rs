@@ -1,5 +1,18 @@
+//! The syntax tree of Brainfuck programs and ways to walk, change, print,
+//! compare and encode it.
+
+mod apply;
+#[allow(clippy::module_inception)]
 mod ast;
+mod builder;
+mod encode;
+mod equal;
+mod print;
 mod walk;
 
+pub use apply::*;
 pub use ast::*;
+pub use builder::*;
+pub use encode::JsonError;
+pub use print::*;
 pub use walk::*;
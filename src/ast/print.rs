@@ -0,0 +1,220 @@
+use crate::ast::ast::{Node, Spanned};
+use crate::ast::walk::{Event, inspect};
+use crate::token::{Pos, Source};
+use std::fmt::{self, Write};
+
+/// The name of the variant of node.
+fn kind(node: &Node) -> &'static str {
+    match node {
+        Node::Program(_) => "Program",
+        Node::IncPtr(_) => "IncPtr",
+        Node::DecPtr(_) => "DecPtr",
+        Node::IncByte(_) => "IncByte",
+        Node::DecByte(_) => "DecByte",
+        Node::OutputByte(_) => "OutputByte",
+        Node::InputByte(_) => "InputByte",
+        Node::DebugDump(_) => "DebugDump",
+        Node::Loop(_) => "Loop",
+        Node::Body(_) => "Body",
+        Node::BadNode(_) => "BadNode",
+    }
+}
+
+// Writes p as line:column resolved in source, or the raw Pos value without
+// a source.
+fn write_pos(w: &mut dyn Write, source: Option<&Source>, p: Pos) -> fmt::Result {
+    match source {
+        _ if !p.is_valid() => w.write_char('-'),
+        Some(source) => {
+            let position = source.position(p);
+            write!(w, "{}:{}", position.line, position.column)
+        }
+        None => write!(w, "{}", p.0),
+    }
+}
+
+// Writes the span of node as pos-end, prefixed with the filename of
+// source if it has one.
+fn write_span(w: &mut dyn Write, source: Option<&Source>, node: &Node) -> fmt::Result {
+    if let Some(source) = source.filter(|s| !s.name().is_empty()) {
+        write!(w, "{}:", source.name())?;
+    }
+    write_pos(w, source, node.pos())?;
+    w.write_char('-')?;
+    write_pos(w, source, node.end())
+}
+
+/// Writes node as an indented tree, one numbered line per node, in the
+/// manner of Go's ast.Fprint. Every line holds the nesting depth of the
+/// node, its kind and its span, followed by fields that are not nodes.
+/// For `+[` and `-]` on two lines:
+///
+/// ```
+/// use rust_brainfuck::ast::{Spanned, fprint};
+/// use rust_brainfuck::parser::parse_file;
+/// use rust_brainfuck::scanner::Mode;
+/// use rust_brainfuck::token::FileSet;
+///
+/// let fset = FileSet::new();
+/// let node = parse_file(&fset, "hello.bf", "+[\n-]", Mode::default()).unwrap();
+/// let mut out = String::new();
+/// fprint(&mut out, fset.file(node.pos()).as_deref(), &node).unwrap();
+/// assert_eq!(
+///     out,
+///     concat!(
+///         "     0  0  Program hello.bf:1:1-2:3 input=0 comments=0\n",
+///         "     1  1  .  Body hello.bf:1:1-2:3 len=2\n",
+///         "     2  2  .  .  IncByte hello.bf:1:1-1:2\n",
+///         "     3  2  .  .  Loop hello.bf:1:2-2:3 rbrack=2:2\n",
+///         "     4  3  .  .  .  Body hello.bf:2:1-2:2 len=1\n",
+///         "     5  4  .  .  .  .  DecByte hello.bf:2:1-2:2\n",
+///     )
+/// );
+/// ```
+///
+/// Positions are resolved in source, which must be the source node was
+/// parsed from; without one, raw Pos values are written.
+pub fn fprint(w: &mut dyn Write, source: Option<&Source>, node: &Node) -> fmt::Result {
+    let mut result = Ok(());
+    let mut line = 0;
+    inspect(node, |node, event| {
+        let Event::Enter { depth } = event else {
+            return true;
+        };
+        if result.is_err() {
+            return false;
+        }
+        result = (|| {
+            write!(w, "{:6} {:2}  ", line, depth)?;
+            for _ in 0..depth {
+                w.write_str(".  ")?;
+            }
+            write!(w, "{} ", kind(node))?;
+            write_span(w, source, node)?;
+            match node {
+                Node::Program(n) => {
+                    write!(w, " input={} comments={}", n.input.len(), n.comments.len())?
+                }
+                Node::Body(n) => write!(w, " len={}", n.list.len())?,
+                Node::Loop(n) => {
+                    w.write_str(" rbrack=")?;
+                    write_pos(w, source, n.rbrack)?
+                }
+                _ => {}
+            }
+            w.write_char('\n')
+        })();
+        line += 1;
+        true
+    });
+    result
+}
+
+/// Writes node as a single-line S-expression followed by a newline. Nodes
+/// without children are written as bare atoms, so that
+///
+/// ```text
+/// (Program (Body IncByte (Loop (Body DecByte))))
+/// ```
+///
+/// is the tree of `+[-]`. With a source, every node is written as a list
+/// starting with its kind and position, as in `(IncByte 1:1)`.
+pub fn fprint_sexpr(w: &mut dyn Write, source: Option<&Source>, node: &Node) -> fmt::Result {
+    let mut result = Ok(());
+    inspect(node, |node, event| {
+        if result.is_err() {
+            return false;
+        }
+        let leaf = !matches!(node, Node::Program(_) | Node::Loop(_) | Node::Body(_));
+        let parens = !leaf || source.is_some();
+        result = (|| match event {
+            Event::Enter { depth } => {
+                if depth > 0 {
+                    w.write_char(' ')?;
+                }
+                if parens {
+                    w.write_char('(')?;
+                }
+                w.write_str(kind(node))?;
+                if source.is_some() {
+                    w.write_char(' ')?;
+                    write_pos(w, source, node.pos())?;
+                }
+                Ok(())
+            }
+            Event::Leave { depth } => {
+                if parens {
+                    w.write_char(')')?;
+                }
+                if depth == 0 {
+                    w.write_char('\n')?;
+                }
+                Ok(())
+            }
+        })();
+        true
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, parse_file, parse_program_from};
+    use crate::scanner::Mode;
+    use crate::token::FileSet;
+    use std::rc::Rc;
+
+    #[test]
+    fn tree() {
+        // The example of the fprint doc comment.
+        let fset = FileSet::new();
+        let node = parse_file(&fset, "hello.bf", "+[\n-]", Mode::default()).unwrap();
+        let mut out = String::new();
+        fprint(&mut out, fset.file(node.pos()).as_deref(), &node).unwrap();
+        assert_eq!(
+            out,
+            concat!(
+                "     0  0  Program hello.bf:1:1-2:3 input=0 comments=0\n",
+                "     1  1  .  Body hello.bf:1:1-2:3 len=2\n",
+                "     2  2  .  .  IncByte hello.bf:1:1-1:2\n",
+                "     3  2  .  .  Loop hello.bf:1:2-2:3 rbrack=2:2\n",
+                "     4  3  .  .  .  Body hello.bf:2:1-2:2 len=1\n",
+                "     5  4  .  .  .  .  DecByte hello.bf:2:1-2:2\n",
+            )
+        );
+
+        // Without a source, raw positions are written.
+        let source = Rc::new(Source::new("", 1, 1));
+        let mut parser = Parser::new(source, b"[", Mode::default());
+        let node = Node::Program(parser.parse_program());
+        let mut out = String::new();
+        fprint(&mut out, None, &node).unwrap();
+        assert_eq!(
+            out,
+            concat!(
+                "     0  0  Program 1-2 input=0 comments=0\n",
+                "     1  1  .  Body 1-2 len=1\n",
+                "     2  2  .  .  Loop 1-2 rbrack=-\n",
+                "     3  3  .  .  .  Body 2-2 len=0\n",
+            )
+        );
+    }
+
+    #[test]
+    fn sexpr() {
+        let mut out = String::new();
+        let node = parse_program_from("+[-]").unwrap();
+        fprint_sexpr(&mut out, None, &node).unwrap();
+        assert_eq!(out, "(Program (Body IncByte (Loop (Body DecByte))))\n");
+
+        let fset = FileSet::new();
+        let node = parse_file(&fset, "", "+\n.", Mode::default()).unwrap();
+        let mut out = String::new();
+        fprint_sexpr(&mut out, fset.file(node.pos()).as_deref(), &node).unwrap();
+        assert_eq!(
+            out,
+            "(Program 1:1 (Body 1:1 (IncByte 1:1) (OutputByte 2:1)))\n"
+        );
+    }
+}
@@ -1,22 +1,340 @@
-use crate::ast::ast::Node;
+use crate::ast::ast::{Body, Node};
+use crate::token;
+use std::mem;
 
+/// Visits the nodes of a tree with [`walk`].
 pub trait Visitor {
+    /// Visits node; returns the visitor for its children, or None to skip them.
     fn visit(&self, node: &Node) -> Option<&dyn Visitor>;
 }
 
+/// Calls v for node and, as long as visits return a visitor, for the
+/// children of the visited nodes in depth-first order.
+// The traversal keeps its own stack of pending nodes so that deeply nested
+// loops cannot overflow the call stack.
 pub fn walk(v: &dyn Visitor, node: &Node) {
-    if let Some(v) = v.visit(node) {
+    let mut stack = vec![(v, node)];
+    while let Some((v, node)) = stack.pop() {
+        if let Some(v) = v.visit(node) {
+            match node {
+                Node::Loop(n) => stack.push((v, &n.body)),
+                Node::Program(n) => stack.push((v, &n.body)),
+                Node::Body(n) => stack.extend(n.list.iter().rev().map(|child| (v, child))),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Tells an [`inspect`] callback whether a node is being entered or left.
+/// The depth of the root node is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The node is entered, before its children.
+    Enter {
+        /// Depth of the node.
+        depth: usize,
+    },
+    /// The node is left, after its children.
+    Leave {
+        /// Depth of the node.
+        depth: usize,
+    },
+}
+
+impl Event {
+    /// Returns the depth of the node.
+    pub fn depth(self) -> usize {
+        match self {
+            Event::Enter { depth } | Event::Leave { depth } => depth,
+        }
+    }
+}
+
+// A pending call of an inspect callback.
+enum Step<'a> {
+    Enter(&'a Node, usize),
+    Leave(&'a Node, usize),
+}
+
+/// Traverses node depth-first, calling f with an Enter event before the
+/// children of every node and a Leave event after them. If f returns false
+/// for an Enter event, the children are skipped and the node is not left;
+/// the result for a Leave event is ignored.
+pub fn inspect<'a>(node: &'a Node, mut f: impl FnMut(&'a Node, Event) -> bool) {
+    let mut stack = vec![Step::Enter(node, 0)];
+    while let Some(step) = stack.pop() {
+        let (node, depth) = match step {
+            Step::Leave(node, depth) => {
+                f(node, Event::Leave { depth });
+                continue;
+            }
+            Step::Enter(node, depth) => (node, depth),
+        };
+        if !f(node, Event::Enter { depth }) {
+            continue;
+        }
+
+        stack.push(Step::Leave(node, depth));
         match node {
-            Node::Loop(n) => walk(v, &n.body),
-            Node::Program(n) => walk(v, &n.body),
-            Node::Body(n) => walk_for_list(v, &n.list),
+            Node::Loop(n) => stack.push(Step::Enter(&n.body, depth + 1)),
+            Node::Program(n) => stack.push(Step::Enter(&n.body, depth + 1)),
+            Node::Body(n) => stack.extend(
+                n.list
+                    .iter()
+                    .rev()
+                    .map(|child| Step::Enter(child, depth + 1)),
+            ),
             _ => {}
         }
     }
 }
 
-fn walk_for_list(v: &dyn Visitor, list: &Vec<Node>) {
-    for child in list {
-        walk(v, child);
+/// A visitor that may change the nodes it visits and keep state between
+/// them.
+pub trait VisitorMut {
+    /// Called before the children of node are walked. Returning false
+    /// skips the children, and leave is not called for node.
+    fn enter(&mut self, _node: &mut Node) -> bool {
+        true
+    }
+
+    /// Called after the children of node have been walked.
+    fn leave(&mut self, _node: &mut Node) {}
+}
+
+// A node whose children are being walked. They are moved out of the node
+// meanwhile, which lets the walk own every node on its stack.
+struct Frame {
+    node: Node,
+    children: std::vec::IntoIter<Node>,
+    walked: Vec<Node>,
+}
+
+// A node that takes the place of children moved out of their parent.
+pub(super) fn placeholder() -> Node {
+    Node::Body(Body {
+        pos: token::NO_POS,
+        list: Vec::new(),
+    })
+}
+
+pub(super) fn detach_children(node: &mut Node) -> Vec<Node> {
+    match node {
+        Node::Program(n) => vec![mem::replace(&mut *n.body, placeholder())],
+        Node::Loop(n) => vec![mem::replace(&mut *n.body, placeholder())],
+        Node::Body(n) => mem::take(&mut n.list),
+        _ => Vec::new(),
+    }
+}
+
+pub(super) fn attach_children(node: &mut Node, mut children: Vec<Node>) {
+    match node {
+        Node::Program(n) => *n.body = children.pop().unwrap(),
+        Node::Loop(n) => *n.body = children.pop().unwrap(),
+        Node::Body(n) => n.list = children,
+        _ => {}
+    }
+}
+
+/// Walks node depth-first, calling enter on every node and leave once its
+/// children are done. A node's children are walked as they are when enter
+/// returns, so enter may replace or restructure them; leave sees the
+/// children after they were walked. Like walk, the traversal does not
+/// recurse.
+pub fn walk_mut(v: &mut dyn VisitorMut, root: &mut Node) {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut next = mem::replace(root, placeholder());
+    loop {
+        if v.enter(&mut next) {
+            let children = detach_children(&mut next);
+            stack.push(Frame {
+                node: next,
+                children: children.into_iter(),
+                walked: Vec::new(),
+            });
+        } else {
+            match stack.last_mut() {
+                Some(parent) => parent.walked.push(next),
+                None => {
+                    *root = next;
+                    return;
+                }
+            }
+        }
+
+        // Leave the nodes whose children are done, up to the next child
+        // still to be entered.
+        loop {
+            let frame = stack.last_mut().unwrap();
+            if let Some(child) = frame.children.next() {
+                next = child;
+                break;
+            }
+
+            let mut frame = stack.pop().unwrap();
+            attach_children(&mut frame.node, frame.walked);
+            v.leave(&mut frame.node);
+            match stack.last_mut() {
+                Some(parent) => parent.walked.push(frame.node),
+                None => {
+                    *root = frame.node;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program_from;
+    use std::cell::Cell;
+
+    struct CountLoops(Cell<usize>);
+
+    impl Visitor for CountLoops {
+        fn visit(&self, node: &Node) -> Option<&dyn Visitor> {
+            if let Node::Loop(_) = node {
+                self.0.set(self.0.get() + 1);
+            }
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let src = "[".repeat(depth) + &"]".repeat(depth);
+        let node = parse_program_from(src).unwrap();
+        let counter = CountLoops(Cell::new(0));
+        walk(&counter, &node);
+        assert_eq!(counter.0.get(), depth);
+    }
+
+    // Turns every `+` into a `-`.
+    struct Negate;
+
+    impl VisitorMut for Negate {
+        fn enter(&mut self, node: &mut Node) -> bool {
+            if let Node::IncByte(n) = node {
+                *node = Node::DecByte(crate::ast::DecByte { pos: n.pos });
+            }
+            true
+        }
+    }
+
+    // Records the order of the enter and leave calls, skipping loops.
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl VisitorMut for Trace {
+        fn enter(&mut self, node: &mut Node) -> bool {
+            let name = format!("{:?}", node);
+            let name = name.split('(').next().unwrap();
+            self.0.push(format!("enter {}", name));
+            !matches!(node, Node::Loop(_))
+        }
+
+        fn leave(&mut self, node: &mut Node) {
+            let name = format!("{:?}", node);
+            self.0
+                .push(format!("leave {}", name.split('(').next().unwrap()));
+        }
+    }
+
+    #[test]
+    fn rewrite() {
+        let mut node = parse_program_from("+[+-]").unwrap();
+        walk_mut(&mut Negate, &mut node);
+        let want = parse_program_from("-[--]").unwrap();
+        assert_eq!(format!("{:?}", node), format!("{:?}", want));
+    }
+
+    #[test]
+    fn enter_and_leave() {
+        let mut node = parse_program_from("+[-]").unwrap();
+        let mut trace = Trace::default();
+        walk_mut(&mut trace, &mut node);
+        assert_eq!(
+            trace.0,
+            [
+                "enter Program",
+                "enter Body",
+                "enter IncByte",
+                "leave IncByte",
+                "enter Loop",
+                "leave Body",
+                "leave Program",
+            ]
+        );
+        // The walk puts every node back where it was.
+        let want = parse_program_from("+[-]").unwrap();
+        assert_eq!(format!("{:?}", node), format!("{:?}", want));
+    }
+
+    #[test]
+    fn deep_nesting_mut() {
+        let depth = 100_000;
+        let src = "+".repeat(depth) + &"[".repeat(depth) + &"+]".repeat(depth);
+        let mut node = parse_program_from(src).unwrap();
+        walk_mut(&mut Negate, &mut node);
+        let counter = CountLoops(Cell::new(0));
+        walk(&counter, &node);
+        assert_eq!(counter.0.get(), depth);
+    }
+
+    #[test]
+    fn inspect_events() {
+        let node = parse_program_from("+[>[-]]").unwrap();
+        let mut max_depth = 0;
+        let mut leaves = 0;
+        inspect(&node, |node, event| {
+            match event {
+                Event::Enter { depth } => max_depth = max_depth.max(depth),
+                Event::Leave { .. } => leaves += 1,
+            }
+            // Skip the children of the inner loop.
+            !matches!(node, Node::Loop(_)) || event.depth() < 4
+        });
+        // Program, Body, Loop, Body, Loop
+        assert_eq!(max_depth, 4);
+        // Every node but the inner loop and its body: Program, Body, +,
+        // Loop, Body and >.
+        assert_eq!(leaves, 6);
+
+        let mut events = Vec::new();
+        inspect(&node, |node, event| {
+            if let Node::Loop(_) | Node::Program(_) = node {
+                events.push(event);
+            }
+            true
+        });
+        assert_eq!(
+            events,
+            [
+                Event::Enter { depth: 0 },
+                Event::Enter { depth: 2 },
+                Event::Enter { depth: 4 },
+                Event::Leave { depth: 4 },
+                Event::Leave { depth: 2 },
+                Event::Leave { depth: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn inspect_deep_nesting() {
+        let depth = 100_000;
+        let src = "[".repeat(depth) + &"]".repeat(depth);
+        let node = parse_program_from(src).unwrap();
+        let mut max_depth = 0;
+        inspect(&node, |_, event| {
+            max_depth = max_depth.max(event.depth());
+            true
+        });
+        // The innermost loop's body.
+        assert_eq!(max_depth, 2 * depth + 1);
     }
 }
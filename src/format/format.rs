@@ -0,0 +1,362 @@
+use crate::ast::{self, Comment, Loop, Node, Spanned, inspect};
+use crate::parser::Parser;
+use crate::scanner::{ErrorList, Mode};
+use crate::token::{Pos, Source};
+use std::rc::Rc;
+
+/// Controls the layout of formatted programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum width of a line of commands. Comments and runs longer than
+    /// a line are not broken up to fit.
+    pub width: usize,
+    /// Number of spaces a loop body is indented by.
+    pub indent: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 2,
+        }
+    }
+}
+
+/// Formats src in the default layout. See [`Config::source`].
+pub fn source(src: &[u8], mode: Mode) -> Result<Vec<u8>, ErrorList> {
+    Config::default().source(src, mode)
+}
+
+impl Config {
+    /// Parses src with mode, keeping comments, and returns it in canonical
+    /// layout. Programs with syntax errors are not formatted.
+    pub fn source(&self, src: &[u8], mode: Mode) -> Result<Vec<u8>, ErrorList> {
+        let source = Rc::new(Source::new("", 1, src.len()));
+        let mut parser = Parser::new(source.clone(), src, mode | Mode::SCAN_COMMENTS);
+        let program = parser.parse_program();
+        let mut errors = parser.errors.take();
+        if errors.has_errors() {
+            errors.sort();
+            return Err(errors);
+        }
+        Ok(self.program(&source, &program))
+    }
+
+    /// Reports whether src is already in canonical layout.
+    pub fn check(&self, src: &[u8], mode: Mode) -> Result<bool, ErrorList> {
+        Ok(self.source(src, mode)? == src)
+    }
+
+    /// Prints program, which was parsed from source, in canonical layout:
+    ///
+    ///  - Commands are packed into lines of at most width columns, breaking
+    ///    only between runs of the same command.
+    ///  - A loop without nested loops or comments is kept on one line if it
+    ///    fits. Other loops open at the end of a line, their bodies are
+    ///    indented by one level and the `]` starts a new line.
+    ///  - A comment that follows a command on the same line stays at the
+    ///    end of that line; other comments get a line of their own.
+    ///  - Input data after a `!` separator is copied as is.
+    ///
+    /// Formatting the output again yields the same output. Comments are only
+    /// kept if the program was parsed with [`Mode::SCAN_COMMENTS`]; bad nodes
+    /// are dropped.
+    pub fn program(&self, source: &Source, program: &ast::Program) -> Vec<u8> {
+        let mut items = Vec::new();
+        inspect(&program.body, |node, event| {
+            match (node, event) {
+                (Node::Loop(l), ast::Event::Enter { .. }) => items.push(Item::Open(l)),
+                (Node::Loop(l), ast::Event::Leave { .. }) => items.push(Item::Close(l)),
+                (_, ast::Event::Enter { .. }) => {
+                    if let Some(cmd) = command(node) {
+                        items.push(Item::Command(cmd, node.pos()));
+                    }
+                }
+                _ => {}
+            }
+            true
+        });
+
+        let mut p = Printer {
+            config: self,
+            source,
+            comments: &program.comments,
+            next_comment: 0,
+            out: Vec::new(),
+            col: 0,
+            depth: 0,
+            last_line: 0,
+        };
+        p.items(&items);
+        p.flush_comments(Pos(usize::MAX));
+        p.newline();
+
+        if source.offset(program.eof) < source.size() {
+            p.out.push(b'!');
+            p.out.extend_from_slice(&program.input);
+        }
+        p.out
+    }
+}
+
+fn command(node: &Node) -> Option<u8> {
+    match node {
+        Node::IncPtr(_) => Some(b'>'),
+        Node::DecPtr(_) => Some(b'<'),
+        Node::IncByte(_) => Some(b'+'),
+        Node::DecByte(_) => Some(b'-'),
+        Node::OutputByte(_) => Some(b'.'),
+        Node::InputByte(_) => Some(b','),
+        Node::DebugDump(_) => Some(b'#'),
+        _ => None,
+    }
+}
+
+// A program flattened into the tokens to print.
+enum Item<'a> {
+    Command(u8, Pos),
+    Open(&'a Loop),
+    Close(&'a Loop),
+}
+
+impl Item<'_> {
+    fn pos(&self) -> Pos {
+        match self {
+            Item::Command(_, pos) => *pos,
+            Item::Open(l) => l.pos,
+            Item::Close(l) if l.rbrack.is_valid() => l.rbrack,
+            Item::Close(l) => l.body.end(),
+        }
+    }
+}
+
+struct Printer<'a> {
+    config: &'a Config,
+    source: &'a Source,
+    comments: &'a [Comment],
+    next_comment: usize,
+    out: Vec<u8>,
+    // width of the current line; indentation is written with the first
+    // text on a line, so the line is empty if col is 0
+    col: usize,
+    depth: usize,
+    // source line of the last printed token
+    last_line: usize,
+}
+
+impl Printer<'_> {
+    fn newline(&mut self) {
+        if self.col > 0 {
+            self.out.push(b'\n');
+            self.col = 0;
+        }
+    }
+
+    fn write(&mut self, text: &[u8]) {
+        if self.col == 0 {
+            let indent = self.depth * self.config.indent;
+            self.out.resize(self.out.len() + indent, b' ');
+            self.col = indent;
+        }
+        self.out.extend_from_slice(text);
+        self.col += text.len();
+    }
+
+    // Starts a new line unless n more columns fit on the current one.
+    fn reserve(&mut self, n: usize) {
+        if self.col > 0 && self.col + n > self.config.width {
+            self.newline();
+        }
+    }
+
+    fn token(&mut self, text: &[u8], pos: Pos) {
+        self.write(text);
+        self.last_line = self.source.line(pos);
+    }
+
+    fn next_comment_pos(&self) -> Option<Pos> {
+        self.comments.get(self.next_comment).map(|c| c.pos)
+    }
+
+    // Prints the comments before pos. Only comments on the line of the last
+    // token are printed if trailing is set.
+    fn comments_before(&mut self, pos: Pos, trailing: bool) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.pos >= pos {
+                break;
+            }
+            let same_line = self.col > 0 && self.source.line(comment.pos) == self.last_line;
+            if trailing && !same_line {
+                break;
+            }
+            if same_line {
+                self.write(b" ");
+            } else {
+                self.newline();
+            }
+            self.write(comment.text.as_bytes());
+            self.newline();
+            self.next_comment += 1;
+        }
+    }
+
+    fn flush_comments(&mut self, pos: Pos) {
+        self.comments_before(pos, false);
+    }
+
+    // Prints the run of commands equal to cmd that starts items, breaking
+    // it only if it is longer than a line. Returns the length of the run.
+    fn run(&mut self, items: &[Item], cmd: u8) -> usize {
+        let limit = self.next_comment_pos().unwrap_or(Pos(usize::MAX));
+        let n = items
+            .iter()
+            .take_while(|item| matches!(item, Item::Command(c, pos) if *c == cmd && *pos < limit))
+            .count();
+        self.reserve(n);
+
+        let mut left = n;
+        while left > 0 {
+            if self.col >= self.config.width {
+                self.newline();
+            }
+            let indent = if self.col == 0 {
+                self.depth * self.config.indent
+            } else {
+                self.col
+            };
+            let chunk = left.min(self.config.width.saturating_sub(indent).max(1));
+            self.write(&vec![cmd; chunk]);
+            left -= chunk;
+        }
+        self.last_line = self.source.line(items[n - 1].pos());
+        n
+    }
+
+    // Reports the width of l printed on one line, if it may be.
+    fn inline_width(&self, l: &Loop) -> Option<usize> {
+        let Node::Body(body) = &*l.body else {
+            return None;
+        };
+        let simple = body.list.iter().all(|node| command(node).is_some());
+        let end = l.end();
+        let commented = self.comments[self.next_comment..]
+            .first()
+            .is_some_and(|c| c.pos < end);
+        let width = body.list.len() + 2;
+        let fits = self.depth * self.config.indent + width <= self.config.width;
+        (simple && !commented && l.rbrack.is_valid() && fits).then_some(width)
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        let mut i = 0;
+        while i < items.len() {
+            let pos = items[i].pos();
+            self.flush_comments(pos);
+            match items[i] {
+                Item::Command(cmd, _) => {
+                    i += self.run(&items[i..], cmd);
+                    continue;
+                }
+                Item::Open(l) => {
+                    if let Some(width) = self.inline_width(l) {
+                        self.reserve(width);
+                        self.token(b"[", l.pos);
+                        i += 1;
+                        while let Item::Command(cmd, _) = items[i] {
+                            i += self.run(&items[i..], cmd);
+                        }
+                        self.token(b"]", l.rbrack);
+                    } else {
+                        self.reserve(1);
+                        self.token(b"[", l.pos);
+                        let next = items.get(i + 1).map_or(Pos(usize::MAX), Item::pos);
+                        self.comments_before(next, true);
+                        self.newline();
+                        self.depth += 1;
+                    }
+                }
+                Item::Close(_) => {
+                    self.newline();
+                    self.depth -= 1;
+                    self.token(b"]", pos);
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::fprint_sexpr;
+    use crate::parser::parse_program_with_mode;
+    use crate::scanner::Code;
+
+    fn fmt(src: &str) -> String {
+        String::from_utf8(source(src.as_bytes(), Mode::default()).unwrap()).unwrap()
+    }
+
+    // Returns the shape of the tree of src and its comments, ignoring
+    // positions.
+    fn shape(src: &[u8]) -> (String, Vec<String>) {
+        let node = parse_program_with_mode(src, Mode::SCAN_COMMENTS).unwrap();
+        let mut out = String::new();
+        fprint_sexpr(&mut out, None, &node).unwrap();
+        let Node::Program(program) = node else {
+            unreachable!()
+        };
+        let comments = program.comments.iter().map(|c| c.text.clone()).collect();
+        (out, comments)
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(fmt("+ + [ - ]"), "++[-]\n");
+        assert_eq!(fmt("+[>[-]<-]"), "+[\n  >[-]<-\n]\n");
+        assert_eq!(fmt("+ # add\n# done\n"), "+ # add\n# done\n");
+        assert_eq!(fmt("[ # c\n-]"), "[ # c\n  -\n]\n");
+        assert_eq!(fmt(""), "");
+    }
+
+    #[test]
+    fn idempotent() {
+        let srcs = [
+            "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+            "# set up\n+++ # three\n[ # loop\n  - # dec\n]\n",
+            ",[.,]",
+            "",
+        ];
+        let config = Config {
+            width: 20,
+            indent: 4,
+        };
+        for src in srcs {
+            let once = config.source(src.as_bytes(), Mode::default()).unwrap();
+            assert_eq!(config.source(&once, Mode::default()).unwrap(), once);
+            assert!(config.check(&once, Mode::default()).unwrap());
+            assert!(
+                once.split(|&b| b == b'\n')
+                    .all(|line| line.len() <= 20 || line.contains(&b'#'))
+            );
+            assert_eq!(shape(src.as_bytes()), shape(&once));
+        }
+    }
+
+    #[test]
+    fn check() {
+        assert!(!Config::default().check(b"+ +", Mode::default()).unwrap());
+        assert!(Config::default().check(b"++\n", Mode::default()).unwrap());
+
+        let err = source(b"+]", Mode::default()).unwrap_err();
+        assert_eq!(err[0].code, Some(Code::UNMATCHED_CLOSE));
+    }
+
+    #[test]
+    fn input() {
+        let src = "+ , .!a b\n";
+        let out = source(src.as_bytes(), Mode::INPUT_SEPARATOR).unwrap();
+        assert_eq!(out, b"+,.\n!a b\n");
+    }
+}
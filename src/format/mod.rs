@@ -0,0 +1,6 @@
+//! Canonical formatting of Brainfuck source.
+
+#[allow(clippy::module_inception)]
+mod format;
+
+pub use format::*;
@@ -0,0 +1,7 @@
+//! Minimal JSON reading and writing.
+
+mod reader;
+mod writer;
+
+pub use reader::*;
+pub use writer::*;
@@ -0,0 +1,423 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+/// A syntax error in a JSON document, or a document that does not have the
+/// expected shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Byte offset in the document at which the error was found.
+    pub offset: usize,
+    /// What was wrong.
+    pub msg: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.msg)
+    }
+}
+
+impl StdError for Error {}
+
+/// A piece of a JSON document as returned by [`Reader::next`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    ObjectStart,
+    ObjectEnd,
+    ArrayStart,
+    ArrayEnd,
+    /// The name of the next member of an object.
+    Key(String),
+    String(String),
+    /// A number, in the notation of the document.
+    Number(String),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Value,
+    // a value or the end of an array that was just opened
+    ValueOrEnd,
+    Key,
+    // a key or the end of an object that was just opened
+    KeyOrEnd,
+    // a comma or the end of the current container
+    Comma,
+    Done,
+}
+
+/// A pull parser for a single JSON document. Documents are read one event
+/// at a time without building a tree, so nesting depth is only limited by
+/// available memory.
+pub struct Reader<'a> {
+    src: &'a [u8],
+    offset: usize,
+    // open containers, true for objects
+    stack: Vec<bool>,
+    expect: Expect,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src: src.as_bytes(),
+            offset: 0,
+            stack: Vec::new(),
+            expect: Expect::Value,
+        }
+    }
+
+    /// Returns the offset of the next unread byte.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns an error at the current offset.
+    pub fn error(&self, msg: impl Into<String>) -> Error {
+        Error {
+            offset: self.offset,
+            msg: msg.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn after_value(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::Done
+        } else {
+            Expect::Comma
+        };
+    }
+
+    fn close(&mut self, object: bool) -> Event {
+        self.offset += 1;
+        self.stack.pop();
+        self.after_value();
+        if object {
+            Event::ObjectEnd
+        } else {
+            Event::ArrayEnd
+        }
+    }
+
+    /// Returns the next event, or None at the end of the document.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<Event>, Error> {
+        loop {
+            self.skip_whitespace();
+            let c = self.peek();
+            let object = self.stack.last().copied();
+            match self.expect {
+                Expect::Done => {
+                    return match c {
+                        None => Ok(None),
+                        Some(_) => Err(self.error("unexpected data after the document")),
+                    };
+                }
+                Expect::Comma => match c {
+                    Some(b',') => {
+                        self.offset += 1;
+                        self.expect = if object == Some(true) {
+                            Expect::Key
+                        } else {
+                            Expect::Value
+                        };
+                    }
+                    Some(b'}') if object == Some(true) => return Ok(Some(self.close(true))),
+                    Some(b']') if object == Some(false) => return Ok(Some(self.close(false))),
+                    _ if object == Some(true) => return Err(self.error("expected ',' or '}'")),
+                    _ => return Err(self.error("expected ',' or ']'")),
+                },
+                Expect::KeyOrEnd if c == Some(b'}') => return Ok(Some(self.close(true))),
+                Expect::Key | Expect::KeyOrEnd => {
+                    if c != Some(b'"') {
+                        return Err(self.error("expected a string key"));
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    if self.peek() != Some(b':') {
+                        return Err(self.error("expected ':'"));
+                    }
+                    self.offset += 1;
+                    self.expect = Expect::Value;
+                    return Ok(Some(Event::Key(key)));
+                }
+                Expect::ValueOrEnd if c == Some(b']') => return Ok(Some(self.close(false))),
+                Expect::Value | Expect::ValueOrEnd => return self.value().map(Some),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Event, Error> {
+        let event = match self.peek() {
+            Some(b'{') => {
+                self.offset += 1;
+                self.stack.push(true);
+                self.expect = Expect::KeyOrEnd;
+                return Ok(Event::ObjectStart);
+            }
+            Some(b'[') => {
+                self.offset += 1;
+                self.stack.push(false);
+                self.expect = Expect::ValueOrEnd;
+                return Ok(Event::ArrayStart);
+            }
+            Some(b'"') => Event::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => Event::Number(self.number()?),
+            Some(b't') => self.literal("true", Event::Bool(true))?,
+            Some(b'f') => self.literal("false", Event::Bool(false))?,
+            Some(b'n') => self.literal("null", Event::Null)?,
+            Some(_) => return Err(self.error("expected a value")),
+            None => return Err(self.error("unexpected end of the document")),
+        };
+        self.after_value();
+        Ok(event)
+    }
+
+    fn literal(&mut self, word: &str, event: Event) -> Result<Event, Error> {
+        if !self.src[self.offset..].starts_with(word.as_bytes()) {
+            return Err(self.error("expected a value"));
+        }
+        self.offset += word.len();
+        Ok(event)
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.offset;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.offset += 1;
+        }
+        self.offset - start
+    }
+
+    fn number(&mut self) -> Result<String, Error> {
+        let start = self.offset;
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+        let int_start = self.offset;
+        if self.digits() == 0 || (self.src[int_start] == b'0' && self.offset - int_start > 1) {
+            return Err(self.error("invalid number"));
+        }
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.offset += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.offset += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        // The document is a str and numbers are ASCII.
+        Ok(String::from_utf8(self.src[start..self.offset].to_vec()).unwrap())
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let digits = self
+            .src
+            .get(self.offset..self.offset + 4)
+            // from_str_radix would also accept a leading '+'.
+            .filter(|d| d.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        match digits {
+            Some(n) => {
+                self.offset += 4;
+                Ok(n)
+            }
+            None => Err(self.error("invalid unicode escape")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.offset += 1;
+        let mut buf = Vec::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.offset += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(e) = self.peek() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.offset += 1;
+                    let ch = match e {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => {
+                            self.offset -= 1;
+                            return Err(self.error("invalid escape sequence"));
+                        }
+                    };
+                    let mut utf8 = [0; 4];
+                    buf.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+                }
+                0x00..=0x1F => {
+                    self.offset -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                _ => buf.push(c),
+            }
+        }
+
+        // Only whole characters of the str were copied.
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    // Decodes the digits of a \u escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        let mut code = self.hex4()?;
+        if (0xD800..0xDC00).contains(&code) {
+            if !self.src[self.offset..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.offset += 2;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+        }
+        char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    /// Skips the rest of a value whose first event has already been read.
+    pub fn skip(&mut self, first: &Event) -> Result<(), Error> {
+        let mut depth = 0;
+        let mut event = Some(first.clone());
+        loop {
+            match event {
+                Some(Event::ObjectStart | Event::ArrayStart) => depth += 1,
+                Some(Event::ObjectEnd | Event::ArrayEnd) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("unexpected end of the document")),
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+            event = self.next()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(src: &str) -> Result<Vec<Event>, Error> {
+        let mut reader = Reader::new(src);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next()? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    fn error(src: &str) -> String {
+        events(src).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn document() {
+        assert_eq!(
+            events(r#" {"a": [1, -2.5e3, true, null], "b": {}} "#).unwrap(),
+            [
+                Event::ObjectStart,
+                Event::Key("a".into()),
+                Event::ArrayStart,
+                Event::Number("1".into()),
+                Event::Number("-2.5e3".into()),
+                Event::Bool(true),
+                Event::Null,
+                Event::ArrayEnd,
+                Event::Key("b".into()),
+                Event::ObjectStart,
+                Event::ObjectEnd,
+                Event::ObjectEnd,
+            ]
+        );
+        assert_eq!(error("[1,]"), "offset 3: expected a value");
+        assert_eq!(error(r#"{"a" 1}"#), "offset 5: expected ':'");
+        assert_eq!(error("[1} "), "offset 2: expected ',' or ']'");
+        assert_eq!(error("01"), "offset 2: invalid number");
+        assert_eq!(error("1 2"), "offset 2: unexpected data after the document");
+        assert_eq!(error("[["), "offset 2: unexpected end of the document");
+    }
+
+    #[test]
+    fn strings() {
+        let string = |src| match events(src) {
+            Ok(events) => match &events[..] {
+                [Event::String(s)] => Ok(s.clone()),
+                _ => panic!("not a string: {:?}", events),
+            },
+            Err(err) => Err(err.to_string()),
+        };
+        assert_eq!(string(r#""a\"\\\/\n\t""#), Ok("a\"\\/\n\t".into()));
+        assert_eq!(string(r#""é😀""#), Ok("é😀".into()));
+        assert_eq!(string("\"\u{e9}\""), Ok("é".into()));
+        // from_str_radix alone would take "+0e9" for a number.
+        assert_eq!(
+            string(r#""\u+0e9""#),
+            Err("offset 3: invalid unicode escape".into())
+        );
+        assert_eq!(
+            string(r#""\u00""#),
+            Err("offset 3: invalid unicode escape".into())
+        );
+        assert_eq!(
+            string(r#""\uD83D""#),
+            Err("offset 7: unpaired surrogate".into())
+        );
+        assert_eq!(
+            string(r#""\uDE00""#),
+            Err("offset 7: unpaired surrogate".into())
+        );
+        assert_eq!(
+            string(r#""\x""#),
+            Err("offset 2: invalid escape sequence".into())
+        );
+        assert_eq!(
+            string("\"a\nb\""),
+            Err("offset 2: control character in string".into())
+        );
+        assert_eq!(string("\"a"), Err("offset 2: unterminated string".into()));
+    }
+
+    #[test]
+    fn skip() {
+        let mut reader = Reader::new(r#"[{"a": [1, {}]}, 2]"#);
+        assert_eq!(reader.next(), Ok(Some(Event::ArrayStart)));
+        let first = reader.next().unwrap().unwrap();
+        reader.skip(&first).unwrap();
+        assert_eq!(reader.next(), Ok(Some(Event::Number("2".into()))));
+    }
+}
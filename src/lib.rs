@@ -0,0 +1,41 @@
+//! A parser and tools for Brainfuck programs, modelled on Go's go/ast,
+//! go/parser and go/format packages.
+//!
+//! Programs are parsed by the [`parser`] into the tree of [`ast`] nodes,
+//! with positions that [`token::Source`] and [`token::FileSet`] resolve to
+//! lines and columns. Problems are reported as a [`scanner::ErrorList`]
+//! that [`scanner::Renderer`] prints with snippets of the source:
+//!
+//! ```
+//! use rust_brainfuck::ast::{self, Node};
+//! use rust_brainfuck::parser::parse_program_from;
+//!
+//! let program = parse_program_from("++[->+<]").unwrap();
+//! let mut loops = 0;
+//! ast::inspect(&program, |node, event| {
+//!     if let (Node::Loop(_), ast::Event::Enter { .. }) = (node, event) {
+//!         loops += 1;
+//!     }
+//!     true
+//! });
+//! assert_eq!(loops, 1);
+//! ```
+//!
+//! The crate does not execute programs.
+//!
+//! The public API follows semantic versioning starting with this release;
+//! [`VERSION`] is the version of the crate that was built.
+
+#![warn(missing_docs)]
+
+pub mod arena;
+pub mod ast;
+pub mod format;
+pub(crate) mod json;
+pub mod parser;
+pub mod scanner;
+pub mod syntax;
+pub mod token;
+
+/// The version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,24 +1,13 @@
-mod ast;
-mod token;
-mod parser;
-mod scanner;
-
 use std::error::Error;
-use std::rc::Rc;
-use crate::ast::{Body, Node, Program, Visitor, walk, IncPtr, Loop, DecByte};
-use crate::parser::parse_program_from;
-
-struct DebugVisitor;
-
-impl Visitor for DebugVisitor {
-    fn visit(&self, node: &Node) -> Option<&dyn Visitor> {
-        println!("Visiting node: {:?}", node);
-        Some(self)
-    }
-}
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use rust_brainfuck::ast::{Node, Spanned, fprint};
+use rust_brainfuck::format;
+use rust_brainfuck::parser::{parse_file, parse_file_with_limit};
+use rust_brainfuck::scanner::{ErrorList, Mode, Renderer};
+use rust_brainfuck::token::FileSet;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let prog = parse_program_from(">++++++++[<+++++++++>-]<.
+const HELLO_WORLD: &str = ">++++++++[<+++++++++>-]<.
 >++++[<+++++++>-]<+.
 +++++++..
 +++.
@@ -29,9 +18,109 @@ fn main() -> Result<(), Box<dyn Error>> {
 +++.
 ------.
 --------.
->>>++++[<++++++++>-]<+.")?;
+>>>++++[<++++++++>-]<+.";
+
+// Prints errors from parsing content with snippets of the source.
+fn report(err: &(dyn Error + 'static), filename: &str, content: &[u8]) {
+    if let Some(list) = err.downcast_ref::<ErrorList>() {
+        let mut renderer = Renderer::new();
+        renderer.add_file(filename, content);
+        renderer.set_color(std::io::stderr().is_terminal());
+        eprintln!("{}", renderer.render_list(list));
+    }
+}
+
+// Formats src, which was read from filename, reporting at most limit
+// errors if it cannot be parsed.
+fn format_source(
+    config: &format::Config,
+    limit: Option<usize>,
+    filename: &str,
+    src: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let fset = FileSet::new();
+    let prog = parse_file_with_limit(&fset, filename, src, Mode::SCAN_COMMENTS, limit)
+        .inspect_err(|err| report(err.as_ref(), filename, src))?;
+    let Node::Program(program) = &prog else {
+        unreachable!()
+    };
+    Ok(config.program(&fset.file(prog.pos()).unwrap(), program))
+}
+
+// Formats the files named in args in place, or stdin to stdout if there
+// are none. With --check, the files that are not formatted are listed
+// instead and the exit status is 1 if there are any. --error-limit N
+// stops reporting syntax errors after the first N.
+fn bffmt(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = format::Config::default();
+    let mut limit = None;
+    let mut check = false;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => config.width = args.next().ok_or("--width needs a value")?.parse()?,
+            "--indent" => config.indent = args.next().ok_or("--indent needs a value")?.parse()?,
+            "--error-limit" => {
+                limit = Some(args.next().ok_or("--error-limit needs a value")?.parse()?)
+            }
+            _ => files.push(arg.as_str()),
+        }
+    }
+
+    if files.is_empty() {
+        let mut src = Vec::new();
+        std::io::stdin().read_to_end(&mut src)?;
+        let out = format_source(&config, limit, "", &src)?;
+        if check {
+            if out != src {
+                println!("<stdin>");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        std::io::stdout().write_all(&out)?;
+        return Ok(());
+    }
+
+    let mut unformatted = false;
+    for filename in files {
+        let src = fs::read(filename)?;
+        let out = format_source(&config, limit, filename, &src)?;
+        if out == src {
+            continue;
+        }
+        if check {
+            println!("{}", filename);
+            unformatted = true;
+        } else {
+            fs::write(filename, out)?;
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "fmt") {
+        return bffmt(&args[1..]);
+    }
+
+    let fset = FileSet::new();
+    let prog = match parse_file(&fset, "hello.bf", HELLO_WORLD, Default::default()) {
+        Ok(prog) => prog,
+        Err(err) => {
+            report(err.as_ref(), "hello.bf", HELLO_WORLD.as_bytes());
+            return Err(err);
+        }
+    };
 
-    let visitor = DebugVisitor;
-    walk(&visitor, &prog);
+    let mut out = String::new();
+    fprint(&mut out, fset.file(prog.pos()).as_deref(), &prog)?;
+    print!("{}", out);
     Ok(())
 }
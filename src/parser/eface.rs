@@ -1,38 +1,57 @@
-use crate::ast::{Body, Node, Program};
-use crate::parser::{Bailout, Parser};
+use crate::ast::Node;
+use crate::parser::Parser;
+use crate::scanner::Mode;
+use crate::token::FileSet;
 use std::error::Error;
-use std::io::Read;
-use std::rc::Rc;
+use std::io::{Cursor, Read};
 
+/// Parses a whole program from src with the default mode.
 pub fn parse_program_from<T: IntoSource>(src: T) -> Result<Node, Box<dyn Error>> {
-    let text = src.into_bytes()?;
-
-    let mut parser = Parser::new(&text);
-    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| parser.parse_program()));
-
-    let prog = match result {
-        Ok(Some(p)) => p,
-        Ok(None) => Program {
-            body: Rc::new(Node::Body(Body {
-                pos: Default::default(),
-                list: vec![],
-            })),
-        },
-        Err(e) => {
-            if let Some(bail) = e.downcast_ref::<Bailout>() {
-                parser.error(bail.pos, &bail.msg);
-            } else {
-                return Err("unexpected parser panic".into());
-            }
-
-            Program {
-                body: Rc::new(Node::Body(Body {
-                    pos: Default::default(),
-                    list: vec![],
-                })),
-            }
-        }
-    };
+    parse_program_with_mode(src, Mode::default())
+}
+
+/// Parses a whole program from src with the given mode.
+pub fn parse_program_with_mode<T: IntoSource>(src: T, mode: Mode) -> Result<Node, Box<dyn Error>> {
+    parse_program_with_limit(src, mode, None)
+}
+
+/// Like parse_program_with_mode, but stops at the first error beyond
+/// limit, as described at [`Parser::set_error_limit`].
+pub fn parse_program_with_limit<T: IntoSource>(
+    src: T,
+    mode: Mode,
+    limit: Option<usize>,
+) -> Result<Node, Box<dyn Error>> {
+    parse_file_with_limit(&FileSet::new(), "", src, mode, limit)
+}
+
+/// Parses the source of a single file, adding it to fset so that the
+/// positions in the returned tree and errors can be resolved later.
+pub fn parse_file<T: IntoSource>(
+    fset: &FileSet,
+    filename: &str,
+    src: T,
+    mode: Mode,
+) -> Result<Node, Box<dyn Error>> {
+    parse_file_with_limit(fset, filename, src, mode, None)
+}
+
+/// Like parse_file, but stops at the first error beyond limit, as
+/// described at [`Parser::set_error_limit`].
+pub fn parse_file_with_limit<T: IntoSource>(
+    fset: &FileSet,
+    filename: &str,
+    src: T,
+    mode: Mode,
+    limit: Option<usize>,
+) -> Result<Node, Box<dyn Error>> {
+    let source = fset.add_file(filename, 0);
+    let mut parser = Parser::from_reader(source, src.into_reader(), mode);
+    parser.set_error_limit(limit);
+    let prog = parser.parse_program();
+    if let Some(err) = parser.take_io_error() {
+        return Err(Box::new(err));
+    }
 
     let mut errors = parser.errors.borrow_mut();
     errors.sort();
@@ -41,32 +60,167 @@ pub fn parse_program_from<T: IntoSource>(src: T) -> Result<Node, Box<dyn Error>>
         .map_or(Ok(Node::Program(prog)), |e| Err(Box::new(e.clone())))
 }
 
+/// A program source that can be read incrementally by the parser.
 pub trait IntoSource {
-    fn into_bytes(self) -> Result<Vec<u8>, Box<dyn Error>>;
+    /// Returns a reader of the source.
+    fn into_reader<'a>(self) -> Box<dyn Read + 'a>
+    where
+        Self: 'a;
 }
 
 impl IntoSource for &str {
-    fn into_bytes(self) -> Result<Vec<u8>, Box<dyn Error>> {
-        Ok(self.as_bytes().to_vec())
+    fn into_reader<'a>(self) -> Box<dyn Read + 'a>
+    where
+        Self: 'a,
+    {
+        Box::new(self.as_bytes())
     }
 }
 
 impl IntoSource for String {
-    fn into_bytes(self) -> Result<Vec<u8>, Box<dyn Error>> {
-        Ok(self.into_bytes())
+    fn into_reader<'a>(self) -> Box<dyn Read + 'a>
+    where
+        Self: 'a,
+    {
+        Box::new(Cursor::new(self.into_bytes()))
     }
 }
 
 impl IntoSource for &[u8] {
-    fn into_bytes(self) -> Result<Vec<u8>, Box<dyn Error>> {
-        Ok(self.to_vec())
+    fn into_reader<'a>(self) -> Box<dyn Read + 'a>
+    where
+        Self: 'a,
+    {
+        Box::new(self)
     }
 }
 
-impl<T: Read + 'static> IntoSource for Box<T> {
-    fn into_bytes(mut self) -> Result<Vec<u8>, Box<dyn Error>> {
-        let mut buf = Vec::new();
-        self.read_to_end(&mut buf)?;
-        Ok(buf)
+impl<T: Read> IntoSource for Box<T> {
+    fn into_reader<'a>(self) -> Box<dyn Read + 'a>
+    where
+        Self: 'a,
+    {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Program;
+
+    fn program(node: &Node) -> &Program {
+        match node {
+            Node::Program(program) => program,
+            _ => panic!("not a program: {:?}", node),
+        }
+    }
+
+    fn body(node: &Node) -> &[Node] {
+        match &*program(node).body {
+            Node::Body(body) => &body.list,
+            body => panic!("not a body: {:?}", body),
+        }
+    }
+
+    #[test]
+    fn debug_dump() {
+        assert!(parse_program_from("+#").is_err());
+
+        let node = parse_program_with_mode("+#", Mode::DEBUG_DUMP).unwrap();
+        assert!(matches!(
+            body(&node),
+            [Node::IncByte(_), Node::DebugDump(_)]
+        ));
+    }
+
+    #[test]
+    fn input_separator() {
+        let mode = Mode::INPUT_SEPARATOR | Mode::DEBUG_DUMP;
+        let node = parse_program_with_mode(",.!]#x", mode).unwrap();
+        assert_eq!(body(&node).len(), 2);
+        assert_eq!(program(&node).input, b"]#x");
+
+        let node = parse_program_with_mode(",.", mode).unwrap();
+        assert!(program(&node).input.is_empty());
+        assert!(parse_program_from(",.!").is_err());
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("read failed"))
+        }
+    }
+
+    #[test]
+    fn read_error() {
+        let err = parse_program_from(Box::new(FailingReader)).unwrap_err();
+        assert_eq!(err.to_string(), "read failed");
+
+        let reader = Box::new("+[-]".as_bytes().chain(FailingReader));
+        assert!(parse_program_from(reader).is_err());
+        assert!(parse_program_from(Box::new("+[-]".as_bytes())).is_ok());
+    }
+
+    #[test]
+    fn file_set() {
+        let fset = FileSet::new();
+        let a = parse_file(&fset, "a.bf", "+[-]", Mode::default()).unwrap();
+        let b = parse_file(&fset, "b.bf", "-", Mode::default()).unwrap();
+        let (Node::Loop(a), Node::DecByte(b)) = (&body(&a)[1], &body(&b)[0]) else {
+            panic!("unexpected nodes");
+        };
+        let (a, b) = (a.pos, b.pos);
+        assert_ne!(a, b);
+        assert_eq!(fset.file(a).unwrap().offset(a), 1);
+        assert_eq!(fset.position(a).filename, "a.bf");
+        assert_eq!(fset.file(b).unwrap().offset(b), 0);
+        assert_eq!(fset.position(b).filename, "b.bf");
+
+        let err = parse_file(&fset, "c.bf", "+]", Mode::default()).unwrap_err();
+        assert!(err.to_string().starts_with("c.bf"), "{}", err);
+        assert_eq!(fset.files().len(), 3);
+    }
+
+    #[test]
+    fn positions() {
+        let fset = FileSet::new();
+        let node = parse_file(&fset, "a.bf", "+\n [-]\n\n", Mode::default()).unwrap();
+        let Node::Loop(l) = &body(&node)[1] else {
+            panic!("expected a loop, found {:?}", body(&node)[1]);
+        };
+        assert_eq!(fset.position(l.pos).to_string(), "a.bf:2:2");
+
+        let source = fset.file(l.pos).unwrap();
+        assert_eq!(source.line_count(), 3);
+        assert_eq!(source.position(source.pos(5)).to_string(), "a.bf:2:4");
+        // The end of the file is at the end of the last line.
+        assert_eq!(source.position(source.pos(8)).to_string(), "a.bf:3:2");
+    }
+
+    #[test]
+    fn error_limit() {
+        let err = parse_program_with_limit("]]]", Mode::default(), Some(1)).unwrap_err();
+        assert_eq!(err.to_string(), "1:1: unmatched ']' (and 1 more errors)");
+        assert!(parse_program_with_limit("+", Mode::default(), Some(0)).is_ok());
+
+        let fset = FileSet::new();
+        let err = parse_file_with_limit(&fset, "a.bf", "]]", Mode::default(), Some(2));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "a.bf:1:1: unmatched ']' (and 1 more errors)"
+        );
+    }
+
+    #[test]
+    fn input_and_comments() {
+        let mode = Mode::INPUT_SEPARATOR | Mode::SCAN_COMMENTS;
+        let node = parse_program_with_mode("+ add one\n.!data", mode).unwrap();
+        let program = program(&node);
+        assert_eq!(program.input, b"data");
+        assert_eq!(program.comments.len(), 1);
+        assert_eq!(program.comments[0].text, "add one");
     }
 }
@@ -0,0 +1,405 @@
+use crate::ast::{self, Node, VisitorMut, walk_mut};
+use crate::parser::Parser;
+use crate::scanner::{Code, Edit, Error, ErrorList, Mode};
+use crate::token::{Pos, Position, Source};
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+
+const BOM: &[u8] = "\u{FEFF}".as_bytes();
+
+/// Keeps a program parsed while its text is edited. An edit inside a loop
+/// only reparses the body of the innermost loop around it; the rest of the
+/// tree is kept and the nodes after the loop are shifted. Edits that change
+/// the structure of the program fall back to parsing the whole text again.
+pub struct IncrementalParser {
+    source: Rc<Source>,
+    text: Vec<u8>,
+    mode: Mode,
+    program: ast::Program,
+    errors: ErrorList,
+}
+
+// The loop found to contain an edit, as offsets into the text before the
+// edit.
+struct Target {
+    // '[' of the enclosing loops, outermost first, ending with the loop's own
+    opens: Vec<usize>,
+    close: usize,
+}
+
+impl IncrementalParser {
+    /// Parses text, whose size source must have.
+    pub fn new(source: Rc<Source>, text: Vec<u8>, mode: Mode) -> Self {
+        let (program, errors) = parse(&source, &text, mode);
+        Self {
+            source,
+            text,
+            mode,
+            program,
+            errors,
+        }
+    }
+
+    /// Returns the source the positions of the program belong to.
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    /// Returns the current text.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Returns the program parsed from the current text.
+    pub fn program(&self) -> &ast::Program {
+        &self.program
+    }
+
+    /// Returns the errors of the current text, sorted by position.
+    pub fn errors(&self) -> &ErrorList {
+        &self.errors
+    }
+
+    /// Applies edit to the text and updates the tree, the errors and the
+    /// line table of the source. Reports whether a single loop body was
+    /// reparsed rather than the whole text.
+    pub fn apply_edit(&mut self, edit: &Edit) -> bool {
+        let Range { start, end } = edit.range;
+        if start > end || end > self.text.len() {
+            panic!(
+                "invalid edit range {:?} (should be within [0, {}])",
+                edit.range,
+                self.text.len()
+            );
+        }
+
+        let target = self.find_loop(start, end);
+        let old_len = self.text.len();
+        self.text.splice(start..end, edit.replacement.bytes());
+        self.source
+            .apply_edit(edit.range.clone(), edit.replacement.as_bytes());
+        if start == old_len && start > 0 && self.text[start - 1] == b'\n' {
+            // A line that starts at the end of the text is not recorded, so
+            // text appended after a final newline needs a new line table.
+            self.source.set_lines_for_content(&self.text);
+        }
+
+        let reparsed = target.is_some_and(|target| self.reparse_loop(target, edit).is_some());
+        if !reparsed {
+            self.reparse_all();
+        }
+        reparsed
+    }
+
+    fn reparse_all(&mut self) {
+        (self.program, self.errors) = parse(&self.source, &self.text, self.mode);
+    }
+
+    // Finds the innermost loop whose brackets enclose start..end without
+    // lying inside of it. Brackets are matched on the text directly: no
+    // other token can contain them.
+    fn find_loop(&self, start: usize, end: usize) -> Option<Target> {
+        // With an input separator the program ends before the input data.
+        let program_end = self.source.offset(self.program.eof);
+        if end > program_end {
+            return None;
+        }
+
+        let mut opens = Vec::new();
+        for (i, &b) in self.text[..start].iter().enumerate() {
+            match b {
+                b'[' => opens.push(i),
+                b']' => {
+                    opens.pop();
+                }
+                _ => {}
+            }
+        }
+
+        // Loops opened at or after start are skipped over by nested.
+        let mut depth = opens.len();
+        let mut nested = 0;
+        for (i, &b) in self.text[start..program_end].iter().enumerate() {
+            match b {
+                b'[' => nested += 1,
+                b']' if nested > 0 => nested -= 1,
+                b']' if depth > 0 => {
+                    depth -= 1;
+                    if start + i >= end {
+                        opens.truncate(depth + 1);
+                        return Some(Target {
+                            opens,
+                            close: start + i,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // Reparses the body of the loop described by target, whose text has
+    // already been edited. Returns None if the edit changed the structure
+    // of the program, in which case it must be parsed again from scratch.
+    fn reparse_loop(&mut self, target: Target, edit: &Edit) -> Option<()> {
+        let open = *target.opens.last().unwrap();
+        let (removed, inserted) = (edit.range.len(), edit.replacement.len());
+        let region = open + 1..target.close - removed + inserted;
+        let text = &self.text[region.clone()];
+        if text.starts_with(BOM)
+            || (self.mode.contains(Mode::INPUT_SEPARATOR) && text.contains(&b'!'))
+        {
+            return None;
+        }
+
+        // A source of its own whose Pos values coincide with the file's.
+        let base = self.source.base();
+        let loop_source = Rc::new(Source::new(
+            self.source.name(),
+            base + region.start,
+            region.len(),
+        ));
+        let mut parser = Parser::new(loop_source, text, self.mode);
+        let body = parser.parse_body();
+        let comments = parser.take_comments();
+        let errors = parser.errors.take();
+        let unbalanced = errors.iter().any(|err| {
+            err.code == Some(Code::UNMATCHED_OPEN) || err.code == Some(Code::UNMATCHED_CLOSE)
+        });
+        if unbalanced {
+            return None;
+        }
+
+        // Everything from the ']' on moves by the difference in length.
+        let mut shift = Shift {
+            threshold: Pos(base + target.close),
+            inserted,
+            removed,
+        };
+
+        // Only the nodes after the loop on the path down to it follow the
+        // edit.
+        let (lp, ancestors) = target.opens.split_last().unwrap();
+        let mut parent = body_mut(&mut self.program.body)?;
+        for &open in ancestors {
+            parent = body_mut(&mut shift_after_loop(parent, Pos(base + open), &mut shift)?.body)?;
+        }
+        *shift_after_loop(parent, Pos(base + lp), &mut shift)?.body = Node::Body(body);
+
+        self.program.eof = shift.pos(self.program.eof);
+
+        let from = Pos(base + open);
+        let comments_at = self.program.comments.partition_point(|c| c.pos < from);
+        self.program
+            .comments
+            .retain(|c| c.pos < from || c.pos >= shift.threshold);
+        for comment in &mut self.program.comments[comments_at..] {
+            comment.pos = shift.pos(comment.pos);
+            comment.end = shift.pos(comment.end);
+        }
+        self.program
+            .comments
+            .splice(comments_at..comments_at, comments);
+
+        let old = mem::take(&mut self.errors);
+        for mut err in old {
+            if err.pos.is_valid() && (open + 1..target.close).contains(&err.pos.offset) {
+                continue;
+            }
+            self.shift_error(&mut err, |offset| {
+                if offset >= target.close {
+                    offset + inserted - removed
+                } else {
+                    offset
+                }
+            });
+            self.errors.push(err);
+        }
+        for mut err in errors {
+            self.shift_error(&mut err, |offset| region.start + offset);
+            self.errors.push(err);
+        }
+        self.errors.sort();
+        Some(())
+    }
+
+    // Moves every position of err to the offset returned by f and resolves
+    // it against the edited source.
+    fn shift_error(&self, err: &mut Error, f: impl Fn(usize) -> usize) {
+        let resolve = |p: &mut Position| {
+            if p.is_valid() {
+                *p = self.source.position(self.source.pos(f(p.offset)));
+            }
+        };
+        resolve(&mut err.pos);
+        resolve(&mut err.end);
+        for label in &mut err.labels {
+            resolve(&mut label.pos);
+            resolve(&mut label.end);
+        }
+        for suggestion in &mut err.suggestions {
+            for edit in &mut suggestion.edits {
+                edit.range = f(edit.range.start)..f(edit.range.end);
+            }
+        }
+    }
+}
+
+fn parse(source: &Rc<Source>, text: &[u8], mode: Mode) -> (ast::Program, ErrorList) {
+    let mut parser = Parser::new(source.clone(), text, mode);
+    let program = parser.parse_program();
+    let mut errors = parser.errors.take();
+    errors.sort();
+    (program, errors)
+}
+
+fn body_mut(node: &mut Node) -> Option<&mut ast::Body> {
+    match node {
+        Node::Body(body) => Some(body),
+        _ => None,
+    }
+}
+
+// Finds the loop at pos in body, whose ']' follows the edit, and shifts it
+// and the nodes after it.
+fn shift_after_loop<'a>(
+    body: &'a mut ast::Body,
+    pos: Pos,
+    shift: &mut Shift,
+) -> Option<&'a mut ast::Loop> {
+    let i = body
+        .list
+        .iter()
+        .rposition(|node| matches!(node, Node::Loop(l) if l.pos == pos))?;
+    let (Node::Loop(l), rest) = body.list[i..].split_first_mut()? else {
+        return None;
+    };
+    l.rbrack = shift.pos(l.rbrack);
+    for node in rest {
+        walk_mut(shift, node);
+    }
+    Some(l)
+}
+
+// Moves every position from threshold on by the length difference of an
+// edit.
+struct Shift {
+    threshold: Pos,
+    inserted: usize,
+    removed: usize,
+}
+
+impl Shift {
+    fn pos(&self, p: Pos) -> Pos {
+        if p >= self.threshold {
+            Pos(p.0 + self.inserted - self.removed)
+        } else {
+            p
+        }
+    }
+}
+
+impl VisitorMut for Shift {
+    fn enter(&mut self, node: &mut Node) -> bool {
+        match node {
+            Node::IncPtr(n) => n.pos = self.pos(n.pos),
+            Node::DecPtr(n) => n.pos = self.pos(n.pos),
+            Node::IncByte(n) => n.pos = self.pos(n.pos),
+            Node::DecByte(n) => n.pos = self.pos(n.pos),
+            Node::OutputByte(n) => n.pos = self.pos(n.pos),
+            Node::InputByte(n) => n.pos = self.pos(n.pos),
+            Node::DebugDump(n) => n.pos = self.pos(n.pos),
+            Node::BadNode(n) => {
+                n.from = self.pos(n.from);
+                n.to = self.pos(n.to);
+            }
+            Node::Loop(n) => {
+                n.pos = self.pos(n.pos);
+                n.rbrack = self.pos(n.rbrack);
+            }
+            Node::Body(n) => n.pos = self.pos(n.pos),
+            Node::Program(n) => n.eof = self.pos(n.eof),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Applies edit to text incrementally and checks that the tree, with its
+    // positions, and the errors are those of parsing the new text from
+    // scratch. Returns whether a single loop body was reparsed.
+    fn check_edit(text: &str, edit: Edit) -> bool {
+        let mode = Mode::SCAN_COMMENTS;
+        let source = Rc::new(Source::new("", 1, text.len()));
+        let mut incremental = IncrementalParser::new(source, text.as_bytes().to_vec(), mode);
+        let reparsed = incremental.apply_edit(&edit);
+
+        let mut want = text.as_bytes().to_vec();
+        want.splice(edit.range.clone(), edit.replacement.bytes());
+        assert_eq!(incremental.text(), want);
+
+        let source = Rc::new(Source::new("", 1, want.len()));
+        let mut parser = Parser::new(source, &want, mode);
+        let program = parser.parse_program();
+        let mut errors = parser.errors().clone();
+        errors.sort();
+
+        assert_eq!(
+            format!("{:?}", incremental.program()),
+            format!("{:?}", program),
+            "{:?}",
+            text
+        );
+        assert_eq!(
+            incremental.errors().to_json_lines(),
+            errors.to_json_lines(),
+            "{:?}",
+            text
+        );
+        reparsed
+    }
+
+    #[test]
+    fn incremental() {
+        let text = b"+[->+<]\n[-]".to_vec();
+        let source = Rc::new(Source::new("", 1, text.len()));
+        let mut parser = IncrementalParser::new(source.clone(), text, Mode::default());
+        assert!(parser.apply_edit(&Edit::insert(3, ">>")));
+        assert_eq!(parser.text(), b"+[->>>+<]\n[-]");
+        assert_eq!(source.size(), 13);
+        assert_eq!(source.position(source.pos(11)).to_string(), "2:2");
+        assert!(parser.errors().is_empty());
+    }
+
+    #[test]
+    fn incremental_edits() {
+        // Deleting a ']' changes the structure.
+        assert!(!check_edit("+[->+<] x\n[-]", Edit::delete(6..7)));
+        assert!(!check_edit("[[-]]", Edit::delete(3..4)));
+        // So does inserting "][" inside a loop.
+        assert!(!check_edit("+[->+<]\n[-] # c", Edit::insert(3, "][")));
+
+        // Appending after a final newline starts a new line.
+        check_edit("+[-]\n", Edit::insert(5, "[>]"));
+        check_edit("+[-]\n", Edit::insert(5, "]"));
+        check_edit("+[-]\n\n", Edit::insert(6, "x\n]"));
+
+        // Edits inside a loop body shift the rest of the tree and comments.
+        assert!(check_edit("+[- a\n>]b\n[-] c", Edit::insert(3, "++\n")));
+        assert!(check_edit("[[-]>[<]]\n# d", Edit::insert(6, "<<")));
+        assert!(check_edit("[[-]>[<]] e", Edit::delete(1..4)));
+        assert!(check_edit("[[-]>[<x]] é", Edit::delete(6..7)));
+        assert!(check_edit(
+            "+[\n  - a\n]\n.",
+            Edit {
+                range: 5..8,
+                replacement: "[>]".to_string(),
+            }
+        ));
+    }
+}
@@ -1,5 +1,11 @@
+//! Parsing of Brainfuck source into [`ast`](crate::ast) trees, either
+//! whole or incrementally as the source is edited.
+
 mod eface;
+mod incremental;
+#[allow(clippy::module_inception)]
 mod parser;
 
 pub use eface::*;
+pub use incremental::*;
 pub use parser::*;
@@ -1,10 +1,13 @@
+use crate::arena;
 use crate::ast;
-use crate::scanner::{ErrorList, Scanner};
+use crate::scanner::{Code, Edit, Error, ErrorHandler, ErrorList, Mode, Scanner};
 use crate::token::{self, Token};
 use std::cell::RefCell;
-use std::panic;
+use std::io::{self, Read};
+use std::mem;
 use std::rc::Rc;
 
+/// A recursive-descent parser of Brainfuck programs.
 pub struct Parser<'a> {
     source: Rc<token::Source>,
     pub(crate) errors: Rc<RefCell<ErrorList>>,
@@ -13,68 +16,121 @@ pub struct Parser<'a> {
     pos: token::Pos,
     tok: Token,
     lit: String,
+    // position just after the current token
+    end: token::Pos,
 
-    nested_lev: usize,
+    error_limit: Option<usize>,
+    comments: Vec<ast::Comment>,
 }
 
-#[derive(Debug)]
-pub(crate) struct Bailout {
-    pub(crate) pos: token::Pos,
-    pub(crate) msg: String,
+fn error_handler(errors: &Rc<RefCell<ErrorList>>) -> ErrorHandler {
+    let errors = errors.clone();
+    Box::new(move |err: Error| {
+        errors.borrow_mut().push(err);
+    })
+}
+
+/// A loop whose closing ']' has not been reached yet.
+struct OpenLoop {
+    pos: token::Pos,
+    body_pos: token::Pos,
+    // nodes of the enclosing list that precede the loop
+    outer: Vec<ast::Node>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(src: &'a [u8]) -> Self {
-        let source = Rc::new(token::Source::new(src.len()));
+    /// Returns a parser of src, whose positions belong to source.
+    pub fn new(source: Rc<token::Source>, src: &'a [u8], mode: Mode) -> Self {
         let errors = Rc::new(RefCell::new(ErrorList::new()));
-        let errors_for_scanner = errors.clone();
-        let eh = move |pos: token::Position, msg: &str| {
-            errors_for_scanner.borrow_mut().add(pos, msg);
-        };
+        let scanner = Scanner::new(source.clone(), src, Some(error_handler(&errors)), mode);
+        Self::with_scanner(source, errors, scanner)
+    }
 
-        let scanner = Scanner::new(source.clone(), src, Some(Box::new(eh)));
+    /// Creates a parser that reads its input from src in chunks as it goes.
+    /// The size of source grows as the input is read.
+    pub fn from_reader(source: Rc<token::Source>, src: impl Read + 'a, mode: Mode) -> Self {
+        let errors = Rc::new(RefCell::new(ErrorList::new()));
+        let scanner = Scanner::from_reader(source.clone(), src, Some(error_handler(&errors)), mode);
+        Self::with_scanner(source, errors, scanner)
+    }
 
+    fn with_scanner(
+        source: Rc<token::Source>,
+        errors: Rc<RefCell<ErrorList>>,
+        scanner: Scanner<'a>,
+    ) -> Self {
         let mut parser = Self {
             source,
             scanner,
             pos: Default::default(),
             tok: Token::ILLEGAL,
             lit: String::new(),
+            end: Default::default(),
             errors,
-            nested_lev: 0,
+            error_limit: None,
+            comments: Vec::new(),
         };
         parser.next();
         parser
     }
 
-    fn next(&mut self) {
-        let (pos, tok, lit) = self.scanner.scan();
-        self.pos = pos;
-        self.tok = tok;
-        self.lit = lit;
+    /// Makes the parser stop at the first error beyond limit, ending the
+    /// error list with a "too many errors" note instead. There is no limit
+    /// by default, and a limit of 0 means no limit either.
+    pub fn set_error_limit(&mut self, limit: Option<usize>) {
+        self.error_limit = limit.filter(|&n| n > 0);
     }
 
-    fn inc_nest_lev(&mut self) {
-        const MAX_NEST_LEV: usize = 100_000;
+    // Reports whether more errors than the limit were found. If so, the
+    // errors beyond the limit are dropped and the note is added.
+    fn reached_error_limit(&mut self) -> bool {
+        let Some(limit) = self.error_limit else {
+            return false;
+        };
 
-        self.nested_lev += 1;
-        if self.nested_lev > MAX_NEST_LEV {
-            self.error(self.pos, "exceeded max nesting depth");
-            panic::panic_any(Bailout {
-                pos: self.pos,
-                msg: "exceeded max nesting depth".to_string(),
-            })
+        let mut errors = self.errors.borrow_mut();
+        if errors.len() <= limit {
+            return false;
         }
+
+        errors.truncate(limit);
+        let note = Error::new(self.source.position(self.pos), "too many errors")
+            .with_code(Code::TOO_MANY_ERRORS);
+        errors.push(note);
+        true
     }
 
-    fn dec_nest_lev(&mut self) {
-        self.nested_lev -= 1;
+    // Advances to the next token, collecting any comments on the way.
+    fn next(&mut self) {
+        loop {
+            let (pos, tok, lit) = self.scanner.scan();
+            if tok == Token::Comment {
+                self.comments.push(ast::Comment {
+                    pos,
+                    end: self.scanner.end(),
+                    text: lit,
+                });
+                continue;
+            }
+
+            self.pos = pos;
+            self.tok = tok;
+            self.lit = lit;
+            self.end = self.scanner.end();
+            break;
+        }
+    }
+
+    fn error(&mut self, pos: token::Pos, code: Code, msg: impl Into<String>) {
+        let err = Error::new(self.source.position(pos), msg).with_code(code);
+        self.errors.borrow_mut().push(err);
     }
 
-    pub(crate) fn error(&mut self, pos: token::Pos, msg: impl Into<String>) {
-        self.errors
-            .borrow_mut()
-            .add(self.source.position(pos), msg.into());
+    // Returns an error spanning the single-byte token at pos.
+    fn token_error(&mut self, pos: token::Pos, code: Code, msg: impl Into<String>) -> Error {
+        Error::new(self.source.position(pos), msg)
+            .with_end(self.source.position(pos + 1usize))
+            .with_code(code)
     }
 
     fn error_expected(&mut self, pos: token::Pos, msg: &str) {
@@ -83,7 +139,7 @@ impl<'a> Parser<'a> {
             message += &format!(", found '{}'", self.tok);
         }
 
-        self.error(pos, message);
+        self.error(pos, Code::UNEXPECTED_TOKEN, message);
     }
 
     fn expect(&mut self, tok: Token) -> token::Pos {
@@ -95,23 +151,6 @@ impl<'a> Parser<'a> {
         pos
     }
 
-    fn expect2(&mut self, tok: Token) -> token::Pos {
-        let pos = if self.tok == tok {
-            self.pos
-        } else {
-            self.error_expected(self.pos, format!("'{}'", tok.to_string()).as_str());
-            token::NO_POS
-        };
-        self.next();
-        pos
-    }
-
-    fn advance(&mut self) {
-        while self.tok != Token::EOF {
-            self.next();
-        }
-    }
-
     fn parse_inc_ptr(&mut self) -> ast::IncPtr {
         ast::IncPtr {
             pos: self.expect(Token::IncPtr),
@@ -148,76 +187,359 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_node_list(&mut self) -> Vec<ast::Node> {
-        let mut list = Vec::new();
-        while self.tok != Token::LoopClose && self.tok != Token::EOF {
-            list.push(self.parse_node());
-        }
-        list
-    }
-
-    fn parse_body(&mut self) -> ast::Body {
-        ast::Body {
-            pos: self.pos,
-            list: self.parse_node_list(),
-        }
-    }
-
-    fn parse_loop(&mut self) -> ast::Loop {
-        let pos = self.pos;
-        self.expect(Token::LoopOpen);
-        let body = self.parse_body();
-        self.expect2(Token::LoopClose);
-
-        ast::Loop {
-            pos,
-            body: Rc::new(ast::Node::Body(body)),
+    fn parse_debug_dump(&mut self) -> ast::DebugDump {
+        ast::DebugDump {
+            pos: self.expect(Token::DebugDump),
         }
     }
 
     fn parse_node(&mut self) -> ast::Node {
-        self.inc_nest_lev();
-        let result = match self.tok {
+        match self.tok {
             Token::IncPtr => ast::Node::IncPtr(self.parse_inc_ptr()),
             Token::DecPtr => ast::Node::DecPtr(self.parse_dec_ptr()),
             Token::IncByte => ast::Node::IncByte(self.parse_inc_byte()),
             Token::DecByte => ast::Node::DecByte(self.parse_dec_byte()),
             Token::OutputByte => ast::Node::OutputByte(self.parse_output_byte()),
             Token::InputByte => ast::Node::InputByte(self.parse_input_byte()),
-            Token::LoopOpen => ast::Node::Loop(self.parse_loop()),
-            _ => {
-                let pos = self.pos;
-                self.error_expected(pos, "node");
-                self.advance();
-                ast::Node::BadNode(ast::BadNode {
-                    from: pos,
-                    to: self.pos,
-                })
+            Token::DebugDump => ast::Node::DebugDump(self.parse_debug_dump()),
+            _ => ast::Node::BadNode(self.parse_bad_node()),
+        }
+    }
+
+    // Reports the token that cannot start a node and skips it.
+    fn parse_bad_node(&mut self) -> ast::BadNode {
+        let pos = self.pos;
+        match self.tok {
+            Token::LoopClose => {
+                let offset = self.source.offset(pos);
+                let err = self
+                    .token_error(pos, Code::UNMATCHED_CLOSE, "unmatched ']'")
+                    .with_suggestion("remove the ']'", vec![Edit::delete(offset..offset + 1)]);
+                self.errors.borrow_mut().push(err);
             }
-        };
-        self.dec_nest_lev();
-        result
+            // The scanner has already reported the illegal character.
+            Token::ILLEGAL => {}
+            _ => self.error_expected(pos, "node"),
+        }
+
+        // Skip just the offending token so that parsing can resume and
+        // later problems are reported in the same pass.
+        let end = self.end;
+        self.next();
+        ast::BadNode { from: pos, to: end }
+    }
+
+    // Reports the loop opened at pos that is still open at EOF.
+    fn error_unmatched_open(&mut self, pos: token::Pos) {
+        // Only EOF can end a body without a ']', so point at the '[' that
+        // is left open rather than at the end of the file.
+        let err = self
+            .token_error(pos, Code::UNMATCHED_OPEN, "unmatched '['")
+            .with_label(
+                self.source.position(self.pos),
+                Default::default(),
+                "expected ']' before the end of the input",
+            )
+            .with_suggestion(
+                "close the loop at the end of the input",
+                vec![Edit::insert(self.source.offset(self.pos), "]")],
+            );
+        self.errors.borrow_mut().push(err);
+    }
+
+    // Ends the innermost open loop at rbrack, which is NO_POS if the loop
+    // has no ']'.
+    fn close_loop(open: OpenLoop, rbrack: token::Pos, list: &mut Vec<ast::Node>) {
+        let body = mem::replace(list, open.outer);
+        list.push(ast::Node::Loop(ast::Loop {
+            pos: open.pos,
+            body: Box::new(ast::Node::Body(ast::Body {
+                pos: open.body_pos,
+                list: body,
+            })),
+            rbrack,
+        }));
     }
 
-    pub fn parse_program(&mut self) -> Option<ast::Program> {
-        if !self.errors.borrow().is_empty() {
-            return None;
+    // Loops are parsed with an explicit stack of open loops instead of
+    // recursion, so nesting depth is only limited by available memory.
+    fn parse_node_list(&mut self) -> Vec<ast::Node> {
+        let mut open_loops: Vec<OpenLoop> = Vec::new();
+        let mut list = Vec::new();
+        loop {
+            if self.reached_error_limit() {
+                // Loops left open are not reported; the parser gave up.
+                while let Some(open) = open_loops.pop() {
+                    Self::close_loop(open, token::NO_POS, &mut list);
+                }
+                return list;
+            }
+
+            match self.tok {
+                Token::LoopOpen => {
+                    let pos = self.expect(Token::LoopOpen);
+                    open_loops.push(OpenLoop {
+                        pos,
+                        body_pos: self.pos,
+                        outer: mem::take(&mut list),
+                    });
+                }
+                Token::LoopClose if !open_loops.is_empty() => {
+                    let rbrack = self.pos;
+                    self.next();
+                    let open = open_loops.pop().unwrap();
+                    Self::close_loop(open, rbrack, &mut list);
+                }
+                Token::EOF => match open_loops.pop() {
+                    Some(open) => {
+                        self.error_unmatched_open(open.pos);
+                        Self::close_loop(open, token::NO_POS, &mut list);
+                    }
+                    None => return list,
+                },
+                _ => list.push(self.parse_node()),
+            }
         }
+    }
 
-        let mut nodes = Vec::new();
-        while self.tok != Token::EOF {
-            nodes.push(self.parse_node());
+    /// Parses the whole input into a program.
+    pub fn parse_program(&mut self) -> ast::Program {
+        let body = self.parse_body();
+
+        ast::Program {
+            pos: self.source.pos(0),
+            body: Box::new(ast::Node::Body(body)),
+            eof: self.pos,
+            input: self.scanner.take_input().unwrap_or_default(),
+            comments: mem::take(&mut self.comments),
         }
+    }
 
-        Some(ast::Program {
-            body: Rc::new(ast::Node::Body(ast::Body {
-                list: nodes,
-                pos: self.pos,
-            })),
-        })
+    /// Parses the program into an [`arena::Tree`], reporting the same
+    /// errors as parse_program.
+    pub fn parse_arena(&mut self) -> arena::Tree {
+        let mut builder = arena::Builder::new();
+        let mut open_loops: Vec<token::Pos> = Vec::new();
+        loop {
+            if self.reached_error_limit() {
+                for _ in open_loops.drain(..) {
+                    builder.close_loop(token::NO_POS);
+                }
+                break;
+            }
+
+            let kind = match self.tok {
+                Token::LoopOpen => {
+                    let pos = self.expect(Token::LoopOpen);
+                    builder.open_loop(pos);
+                    open_loops.push(pos);
+                    continue;
+                }
+                Token::LoopClose if !open_loops.is_empty() => {
+                    builder.close_loop(self.pos);
+                    open_loops.pop();
+                    self.next();
+                    continue;
+                }
+                Token::EOF => match open_loops.pop() {
+                    Some(pos) => {
+                        self.error_unmatched_open(pos);
+                        builder.close_loop(token::NO_POS);
+                        continue;
+                    }
+                    None => break,
+                },
+                Token::IncPtr => arena::Kind::IncPtr,
+                Token::DecPtr => arena::Kind::DecPtr,
+                Token::IncByte => arena::Kind::IncByte,
+                Token::DecByte => arena::Kind::DecByte,
+                Token::OutputByte => arena::Kind::OutputByte,
+                Token::InputByte => arena::Kind::InputByte,
+                Token::DebugDump => arena::Kind::DebugDump,
+                _ => {
+                    let bad = self.parse_bad_node();
+                    builder.bad_node(bad.from, bad.to);
+                    continue;
+                }
+            };
+            builder.push(kind, self.pos);
+            self.next();
+        }
+
+        builder.finish(
+            self.source.pos(0),
+            self.pos,
+            self.scanner.take_input().unwrap_or_default(),
+            mem::take(&mut self.comments),
+        )
+    }
+
+    /// Parses the nodes up to the end of the input, which is also how a
+    /// loop body is reparsed on its own.
+    pub(crate) fn parse_body(&mut self) -> ast::Body {
+        let pos = self.pos;
+        let list = self.parse_node_list();
+        ast::Body { pos, list }
+    }
+
+    pub(crate) fn take_comments(&mut self) -> Vec<ast::Comment> {
+        mem::take(&mut self.comments)
+    }
+
+    /// Takes the error that ended reading the input early, if any.
+    pub fn take_io_error(&mut self) -> Option<io::Error> {
+        self.scanner.take_io_error()
     }
 
+    /// Returns the errors found so far.
     pub fn errors(&self) -> std::cell::Ref<'_, ErrorList> {
         self.errors.borrow()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Spanned;
+
+    // Parses src and returns the program with the errors found.
+    fn parse(src: impl AsRef<[u8]>) -> (ast::Program, String) {
+        let src = src.as_ref();
+        let source = Rc::new(token::Source::new("", 1, src.len()));
+        let mut parser = Parser::new(source, src, Mode::default());
+        let program = parser.parse_program();
+        let mut errors = parser.errors().clone();
+        errors.sort();
+        (program, errors.to_string())
+    }
+
+    fn body(program: &ast::Program) -> &[ast::Node] {
+        match &*program.body {
+            ast::Node::Body(body) => &body.list,
+            body => panic!("not a body: {:?}", body),
+        }
+    }
+
+    #[test]
+    fn unmatched_brackets() {
+        let (program, errors) = parse("+]+[-");
+        assert_eq!(errors, "1:2: unmatched ']' (and 1 more errors)");
+        assert!(matches!(
+            body(&program),
+            [
+                ast::Node::IncByte(_),
+                ast::Node::BadNode(_),
+                ast::Node::IncByte(_),
+                ast::Node::Loop(_)
+            ]
+        ));
+
+        let (_, errors) = parse("+]");
+        assert_eq!(errors, "1:2: unmatched ']'");
+    }
+
+    #[test]
+    fn bad_node_span() {
+        // The illegal character is two bytes wide.
+        let (program, _) = parse("+é+");
+        let ast::Node::BadNode(bad) = &body(&program)[1] else {
+            panic!("expected a bad node, found {:?}", body(&program)[1]);
+        };
+        assert_eq!((bad.from, bad.to), (token::Pos(2), token::Pos(4)));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let (program, errors) = parse("[".repeat(depth) + &"]".repeat(depth));
+        assert_eq!(errors, "no errors");
+
+        let mut node = &body(&program)[0];
+        for _ in 1..depth {
+            let ast::Node::Loop(l) = node else {
+                panic!("expected a loop, found {:?}", node);
+            };
+            let ast::Node::Body(body) = &*l.body else {
+                panic!("not a body: {:?}", l.body);
+            };
+            node = &body.list[0];
+        }
+
+        let (_, errors) = parse("[".repeat(depth));
+        assert_eq!(errors, "1:1: unmatched '[' (and 99999 more errors)");
+    }
+
+    #[test]
+    fn illegal_bytes() {
+        // Reported once by the scanner, not again as illegal characters.
+        let (_, errors) = parse(b"+\xff+");
+        assert_eq!(errors, "1:2: illegal UTF-8 encoding");
+        let (_, errors) = parse(b"+\0+");
+        assert_eq!(errors, "1:2: illegal character NUL");
+        let (_, errors) = parse("+\u{FEFF}+");
+        assert_eq!(errors, "1:2: illegal byte order mark");
+    }
+
+    #[test]
+    fn error_limit() {
+        let limited = |src: &[u8], limit| {
+            let mut parser = Parser::new(
+                Rc::new(token::Source::new("", 1, src.len())),
+                src,
+                Mode::default(),
+            );
+            parser.set_error_limit(limit);
+            parser.parse_program();
+            let errors = parser.errors();
+            errors.iter().filter_map(|err| err.code).collect::<Vec<_>>()
+        };
+        let close = Code::UNMATCHED_CLOSE;
+
+        assert_eq!(
+            limited(b"]]]]", Some(2)),
+            vec![close, close, Code::TOO_MANY_ERRORS]
+        );
+        // No note unless an error beyond the limit was found.
+        assert_eq!(limited(b"]]+", Some(2)), vec![close, close]);
+        // A limit of 0 is no limit.
+        assert_eq!(limited(b"+", Some(0)), vec![]);
+        assert_eq!(limited(b"]]]", Some(0)), vec![close, close, close]);
+        assert_eq!(limited(b"]]]", None), vec![close, close, close]);
+    }
+
+    #[test]
+    fn comment_span() {
+        // The invalid byte is one byte wide but decoded as three.
+        let src = b"+ a\xffb  \n";
+        let source = Rc::new(token::Source::new("", 1, src.len()));
+        let mut parser = Parser::new(source.clone(), src, Mode::SCAN_COMMENTS);
+        let program = parser.parse_program();
+        let comment = &program.comments[0];
+        assert_eq!(comment.text, "a\u{FFFD}b");
+        assert_eq!(source.offset(comment.pos), 2);
+        assert_eq!(source.offset(comment.end), 5);
+    }
+
+    #[test]
+    fn spans() {
+        let offsets = |node: &dyn Spanned| (node.pos().0 - 1, node.end().0 - 1);
+
+        let (program, _) = parse("+[->+<]\n[] ");
+        assert_eq!(offsets(&program), (0, 11));
+        assert_eq!(offsets(&*program.body), (0, 10));
+        let [_, l, ast::Node::Loop(empty)] = body(&program) else {
+            panic!("unexpected body {:?}", body(&program));
+        };
+        assert_eq!(offsets(l), (1, 7));
+        assert_eq!(offsets(empty), (8, 10));
+        assert_eq!(offsets(&*empty.body), (9, 9));
+
+        // An unclosed loop ends where its body does.
+        let (program, _) = parse("[+ ");
+        let [ast::Node::Loop(l)] = body(&program) else {
+            panic!("unexpected body {:?}", body(&program));
+        };
+        assert_eq!(l.rbrack, token::NO_POS);
+        assert_eq!(offsets(l), (0, 2));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::scanner::ErrorList;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Severity {
+    /// Information that helps understand another diagnostic.
+    Note,
+    /// A likely mistake that does not stop the program from being parsed.
+    Warning,
+    /// A problem in the program.
+    #[default]
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A stable diagnostic code, printed as `BF0001`. Codes are never reused,
+/// so tools may filter on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Code(pub u16);
+
+struct CodeInfo {
+    code: Code,
+    severity: Severity,
+    summary: &'static str,
+    explanation: &'static str,
+}
+
+const CODES: &[CodeInfo] = &[
+    CodeInfo {
+        code: Code::UNMATCHED_CLOSE,
+        severity: Severity::Error,
+        summary: "unmatched ']'",
+        explanation: "A ']' was found without a '[' before it that it could close. \
+Remove the ']' or add the missing '[' earlier in the program.",
+    },
+    CodeInfo {
+        code: Code::UNMATCHED_OPEN,
+        severity: Severity::Error,
+        summary: "unmatched '['",
+        explanation: "A '[' is never closed before the end of the program. \
+Add the missing ']' where the loop should end.",
+    },
+    CodeInfo {
+        code: Code::ILLEGAL_CHARACTER,
+        severity: Severity::Error,
+        summary: "illegal character",
+        explanation: "The program contains a character that is not a Brainfuck command. \
+Only the characters '><+-.,[]' (and '#' and '!' when enabled) may appear, \
+separated by whitespace.",
+    },
+    CodeInfo {
+        code: Code::ILLEGAL_NUL,
+        severity: Severity::Error,
+        summary: "illegal character NUL",
+        explanation: "The program contains a NUL byte, which usually means a binary \
+file was passed by mistake.",
+    },
+    CodeInfo {
+        code: Code::ILLEGAL_ENCODING,
+        severity: Severity::Error,
+        summary: "illegal UTF-8 encoding",
+        explanation: "The program contains bytes that are not valid UTF-8. \
+Source files must be UTF-8 encoded.",
+    },
+    CodeInfo {
+        code: Code::ILLEGAL_BOM,
+        severity: Severity::Error,
+        summary: "illegal byte order mark",
+        explanation: "A byte order mark (U+FEFF) may only appear as the very first \
+character of a file.",
+    },
+    CodeInfo {
+        code: Code::UNEXPECTED_TOKEN,
+        severity: Severity::Error,
+        summary: "unexpected token",
+        explanation: "The parser found a token where a command was expected.",
+    },
+    CodeInfo {
+        code: Code::TOO_MANY_ERRORS,
+        severity: Severity::Note,
+        summary: "too many errors",
+        explanation: "The parser stopped after reaching its error limit, so the rest \
+of the input was not checked. Fix the reported errors or raise the limit to see more.",
+    },
+];
+
+impl Code {
+    /// An unmatched `]`.
+    pub const UNMATCHED_CLOSE: Code = Code(1);
+    /// An unmatched `[`.
+    pub const UNMATCHED_OPEN: Code = Code(2);
+    /// A character that is not allowed.
+    pub const ILLEGAL_CHARACTER: Code = Code(3);
+    /// A NUL character.
+    pub const ILLEGAL_NUL: Code = Code(4);
+    /// Invalid UTF-8.
+    pub const ILLEGAL_ENCODING: Code = Code(5);
+    /// A byte order mark after the start of the file.
+    pub const ILLEGAL_BOM: Code = Code(6);
+    /// A token where it is not allowed.
+    pub const UNEXPECTED_TOKEN: Code = Code(7);
+    /// The error limit was reached.
+    pub const TOO_MANY_ERRORS: Code = Code(8);
+
+    fn info(self) -> Option<&'static CodeInfo> {
+        CODES.iter().find(|info| info.code == self)
+    }
+
+    /// Returns all known codes in ascending order.
+    pub fn all() -> impl Iterator<Item = Code> {
+        CODES.iter().map(|info| info.code)
+    }
+
+    /// Returns the severity diagnostics with this code have unless
+    /// configured otherwise.
+    pub fn default_severity(self) -> Severity {
+        self.info().map_or(Severity::Error, |info| info.severity)
+    }
+
+    /// Returns a one-line summary of the code.
+    pub fn summary(self) -> Option<&'static str> {
+        self.info().map(|info| info.summary)
+    }
+
+    /// Returns a longer explanation of the code and how to fix it.
+    pub fn explanation(self) -> Option<&'static str> {
+        self.info().map(|info| info.explanation)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BF{:04}", self.0)
+    }
+}
+
+impl FromStr for Code {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix("BF")
+            .filter(|n| n.len() == 4 && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse().ok())
+            .map(Code)
+            .ok_or_else(|| format!("invalid diagnostic code {:?}", s))
+    }
+}
+
+/// What to do with diagnostics of a given code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Drop the diagnostics.
+    Allow,
+    /// Report the diagnostics as warnings.
+    Warn,
+    /// Report the diagnostics as errors.
+    Deny,
+}
+
+/// Per-code allow/warn/deny configuration applied to an [`ErrorList`].
+/// Diagnostics without a configured level keep their severity.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Code, Level>,
+}
+
+impl LintLevels {
+    /// Returns a configuration without any levels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the level of code.
+    pub fn set(&mut self, code: Code, level: Level) {
+        self.levels.insert(code, level);
+    }
+
+    /// Returns the level of code, if set.
+    pub fn get(&self, code: Code) -> Option<Level> {
+        self.levels.get(&code).copied()
+    }
+
+    /// Drops allowed diagnostics from list and adjusts the severity of
+    /// warned and denied ones. Notes are left alone.
+    pub fn apply(&self, list: &mut ErrorList) {
+        list.retain_mut(|err| {
+            if err.severity == Severity::Note {
+                return true;
+            }
+
+            match err.code.and_then(|code| self.get(code)) {
+                Some(Level::Allow) => return false,
+                Some(Level::Warn) => err.severity = Severity::Warning,
+                Some(Level::Deny) => err.severity = Severity::Error,
+                None => {}
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_file;
+    use crate::scanner::Mode;
+    use crate::token::FileSet;
+
+    #[test]
+    fn codes() {
+        assert_eq!(Code::UNMATCHED_OPEN.to_string(), "BF0002");
+        assert_eq!("BF0001".parse::<Code>().unwrap(), Code::UNMATCHED_CLOSE);
+        for s in ["BF", "BF001", "BF00001", "bf0001", "BF+001", "BF 001"] {
+            assert!(s.parse::<Code>().is_err(), "{}", s);
+        }
+        for code in Code::all() {
+            assert!(code.summary().is_some(), "{}", code);
+            assert!(code.explanation().is_some(), "{}", code);
+            assert_eq!(code.to_string().parse::<Code>().unwrap(), code);
+        }
+        assert_eq!(Code(999).summary(), None);
+        assert_eq!(Code(999).default_severity(), Severity::Error);
+    }
+
+    #[test]
+    fn lint_levels() {
+        let fset = FileSet::new();
+        let err = parse_file(&fset, "", "+]", Mode::default()).unwrap_err();
+        let mut list = *err.downcast::<ErrorList>().unwrap();
+        let severity = |list: &ErrorList| list[0].severity;
+        assert_eq!(severity(&list), Severity::Error);
+
+        let mut levels = LintLevels::new();
+        levels.set(Code::UNMATCHED_CLOSE, Level::Warn);
+        levels.apply(&mut list);
+        assert_eq!(severity(&list), Severity::Warning);
+
+        levels.set(Code::UNMATCHED_CLOSE, Level::Deny);
+        levels.apply(&mut list);
+        assert_eq!(severity(&list), Severity::Error);
+
+        levels.set(Code::UNMATCHED_CLOSE, Level::Allow);
+        levels.apply(&mut list);
+        assert!(list.is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use crate::json;
+use crate::scanner::{Code, Error, ErrorList, Severity, Suggestion};
+use crate::token::Position;
+use std::fmt::{self, Write};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn number(n: Option<usize>) -> String {
+    n.map_or_else(|| "null".to_string(), |n| n.to_string())
+}
+
+fn filename(pos: &Position) -> String {
+    if pos.filename.is_empty() {
+        "null".to_string()
+    } else {
+        json::quote(&pos.filename)
+    }
+}
+
+// Returns end if it is known and lies after pos in the same file.
+fn span_end<'p>(pos: &Position, end: &'p Position) -> Option<&'p Position> {
+    let valid = end.is_valid() && end.filename == pos.filename && end.offset >= pos.offset;
+    valid.then_some(end)
+}
+
+impl ErrorList {
+    /// Writes every error as a JSON object on its own line:
+    ///
+    /// ```text
+    /// {"file":"hello.bf","line":3,"column":14,"end_line":3,"end_column":15,"offset":40,"end_offset":41,"severity":"error","code":"BF0001","message":"unmatched ']'","suggestions":[{"message":"remove the ']'","edits":[{"offset":40,"end_offset":41,"replacement":""}]}]}
+    /// ```
+    ///
+    /// Lines and columns are 1-based, columns count bytes. Unknown values,
+    /// including the code of uncoded diagnostics, are null.
+    pub fn write_json_lines(&self, w: &mut dyn Write) -> fmt::Result {
+        for err in self {
+            let valid = err.pos.is_valid();
+            let end = span_end(&err.pos, &err.end);
+            writeln!(
+                w,
+                "{{\"file\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{},\"offset\":{},\"end_offset\":{},\"severity\":\"{}\",\"code\":{},\"message\":{},\"suggestions\":{}}}",
+                filename(&err.pos),
+                number(valid.then_some(err.pos.line)),
+                number(valid.then_some(err.pos.column)),
+                number(end.map(|e| e.line)),
+                number(end.map(|e| e.column)),
+                number(valid.then_some(err.pos.offset)),
+                number(end.map(|e| e.offset)),
+                err.severity,
+                err.code
+                    .map_or_else(|| "null".to_string(), |c| json::quote(&c.to_string())),
+                json::quote(&err.msg),
+                suggestions_json(err),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the list as written by write_json_lines.
+    pub fn to_json_lines(&self) -> String {
+        let mut s = String::new();
+        self.write_json_lines(&mut s).unwrap();
+        s
+    }
+
+    /// Writes the errors as a SARIF 2.1.0 log with a single run. Every code
+    /// is described as a rule of the tool. Regions carry lines and byte
+    /// offsets, and columns in UTF-16 code units if the content of their
+    /// file is among files, pairs of a filename and its content.
+    pub fn write_sarif(&self, w: &mut dyn Write, files: &[(&str, &[u8])]) -> fmt::Result {
+        write!(
+            w,
+            "{{\"version\":\"2.1.0\",\"$schema\":{},\"runs\":[{{\"columnKind\":\"utf16CodeUnits\",\"tool\":{{\"driver\":{{\"name\":{},\"version\":{},\"rules\":[",
+            json::quote(SARIF_SCHEMA),
+            json::quote(env!("CARGO_PKG_NAME")),
+            json::quote(env!("CARGO_PKG_VERSION")),
+        )?;
+        for (i, code) in Code::all().enumerate() {
+            if i > 0 {
+                w.write_char(',')?;
+            }
+            write!(
+                w,
+                "{{\"id\":{},\"shortDescription\":{{\"text\":{}}},\"fullDescription\":{{\"text\":{}}},\"defaultConfiguration\":{{\"level\":\"{}\"}}}}",
+                json::quote(&code.to_string()),
+                json::quote(code.summary().unwrap_or_default()),
+                json::quote(code.explanation().unwrap_or_default()),
+                sarif_level(code.default_severity()),
+            )?;
+        }
+        w.write_str("]}},\"results\":[")?;
+
+        for (i, err) in self.iter().enumerate() {
+            if i > 0 {
+                w.write_char(',')?;
+            }
+
+            write!(w, "{{\"level\":\"{}\"", sarif_level(err.severity))?;
+            if let Some(code) = err.code {
+                write!(w, ",\"ruleId\":{}", json::quote(&code.to_string()))?;
+            }
+            write!(w, ",\"message\":{{\"text\":{}}}", json::quote(&err.msg))?;
+            if err.pos.is_valid() {
+                w.write_str(",\"locations\":[")?;
+                let end = span_end(&err.pos, &err.end);
+                write_location(w, files, &err.pos, end, None)?;
+                w.write_char(']')?;
+            }
+
+            let labels: Vec<_> = err.labels.iter().filter(|l| l.pos.is_valid()).collect();
+            if !labels.is_empty() {
+                w.write_str(",\"relatedLocations\":[")?;
+                for (j, label) in labels.iter().enumerate() {
+                    if j > 0 {
+                        w.write_char(',')?;
+                    }
+                    let end = span_end(&label.pos, &label.end);
+                    write_location(w, files, &label.pos, end, Some(&label.msg))?;
+                }
+                w.write_char(']')?;
+            }
+
+            if !err.suggestions.is_empty() && !err.pos.filename.is_empty() {
+                w.write_str(",\"fixes\":[")?;
+                for (j, suggestion) in err.suggestions.iter().enumerate() {
+                    if j > 0 {
+                        w.write_char(',')?;
+                    }
+                    write_fix(w, &err.pos.filename, suggestion)?;
+                }
+                w.write_char(']')?;
+            }
+            w.write_char('}')?;
+        }
+
+        w.write_str("]}]}\n")
+    }
+
+    /// Returns the list as written by write_sarif.
+    pub fn to_sarif(&self, files: &[(&str, &[u8])]) -> String {
+        let mut s = String::new();
+        self.write_sarif(&mut s, files).unwrap();
+        s
+    }
+}
+
+// Returns filename as a relative URI reference, percent-encoding every
+// byte other than the unreserved characters and '/'.
+fn uri(filename: &str) -> String {
+    let mut s = String::with_capacity(filename.len());
+    for b in filename.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'/') {
+            s.push(b as char);
+        } else {
+            write!(s, "%{:02X}", b).unwrap();
+        }
+    }
+    s
+}
+
+// Returns the column of pos in UTF-16 code units, the way SARIF counts
+// them, if the content of its file is among files.
+fn utf16_column(files: &[(&str, &[u8])], pos: &Position) -> Option<usize> {
+    let (_, content) = files.iter().find(|(name, _)| *name == pos.filename)?;
+    let line_start = pos.offset.checked_sub(pos.column.checked_sub(1)?)?;
+    let text = content.get(line_start..pos.offset)?;
+    Some(String::from_utf8_lossy(text).encode_utf16().count() + 1)
+}
+
+fn suggestions_json(err: &Error) -> String {
+    let mut s = String::from("[");
+    for (i, suggestion) in err.suggestions.iter().enumerate() {
+        if i > 0 {
+            s.push(',');
+        }
+        write!(
+            s,
+            "{{\"message\":{},\"edits\":[",
+            json::quote(&suggestion.msg)
+        )
+        .unwrap();
+        for (j, edit) in suggestion.edits.iter().enumerate() {
+            if j > 0 {
+                s.push(',');
+            }
+            write!(
+                s,
+                "{{\"offset\":{},\"end_offset\":{},\"replacement\":{}}}",
+                edit.range.start,
+                edit.range.end,
+                json::quote(&edit.replacement)
+            )
+            .unwrap();
+        }
+        s.push_str("]}");
+    }
+    s.push(']');
+    s
+}
+
+fn write_fix(w: &mut dyn Write, filename: &str, suggestion: &Suggestion) -> fmt::Result {
+    write!(
+        w,
+        "{{\"description\":{{\"text\":{}}},\"artifactChanges\":[{{\"artifactLocation\":{{\"uri\":{}}},\"replacements\":[",
+        json::quote(&suggestion.msg),
+        json::quote(&uri(filename))
+    )?;
+    for (i, edit) in suggestion.edits.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        write!(
+            w,
+            "{{\"deletedRegion\":{{\"byteOffset\":{},\"byteLength\":{}}},\"insertedContent\":{{\"text\":{}}}}}",
+            edit.range.start,
+            edit.range.len(),
+            json::quote(&edit.replacement)
+        )?;
+    }
+    w.write_str("]}]}")
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+fn write_location(
+    w: &mut dyn Write,
+    files: &[(&str, &[u8])],
+    pos: &Position,
+    end: Option<&Position>,
+    msg: Option<&str>,
+) -> fmt::Result {
+    w.write_str("{\"physicalLocation\":{")?;
+    if !pos.filename.is_empty() {
+        write!(
+            w,
+            "\"artifactLocation\":{{\"uri\":{}}},",
+            json::quote(&uri(&pos.filename))
+        )?;
+    }
+
+    write!(w, "\"region\":{{\"startLine\":{}", pos.line)?;
+    if let Some(column) = utf16_column(files, pos) {
+        write!(w, ",\"startColumn\":{}", column)?;
+    }
+    write!(w, ",\"byteOffset\":{}", pos.offset)?;
+    if let Some(end) = end {
+        write!(w, ",\"endLine\":{}", end.line)?;
+        if let Some(column) = utf16_column(files, end) {
+            write!(w, ",\"endColumn\":{}", column)?;
+        }
+        write!(w, ",\"byteLength\":{}", end.offset - pos.offset)?;
+    }
+    w.write_str("}}")?;
+
+    if let Some(msg) = msg {
+        write!(w, ",\"message\":{{\"text\":{}}}", json::quote(msg))?;
+    }
+    w.write_char('}')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_file;
+    use crate::scanner::{ErrorList, Mode};
+    use crate::token::FileSet;
+
+    fn errors(filename: &str, src: &str) -> ErrorList {
+        let fset = FileSet::new();
+        let err = parse_file(&fset, filename, src, Mode::default()).unwrap_err();
+        err.downcast::<ErrorList>().map(|list| *list).unwrap()
+    }
+
+    #[test]
+    fn json_lines() {
+        let list = errors("a\"b.bf", "+\n+]");
+        assert_eq!(
+            list.to_json_lines(),
+            concat!(
+                r#"{"file":"a\"b.bf","line":2,"column":2,"end_line":2,"end_column":3,"#,
+                r#""offset":3,"end_offset":4,"severity":"error","code":"BF0001","#,
+                r#""message":"unmatched ']'","suggestions":[{"message":"remove the ']'","#,
+                r#""edits":[{"offset":3,"end_offset":4,"replacement":""}]}]}"#,
+                "\n"
+            )
+        );
+        assert_eq!(ErrorList::new().to_json_lines(), "");
+    }
+
+    #[test]
+    fn sarif() {
+        let filename = "dir/my file.bf";
+        let src = "+ é ]";
+        let list = errors(filename, src);
+
+        let sarif = list.to_sarif(&[(filename, src.as_bytes())]);
+        assert!(sarif.starts_with(r#"{"version":"2.1.0","#), "{}", sarif);
+        assert!(
+            sarif.contains(r#""columnKind":"utf16CodeUnits""#),
+            "{}",
+            sarif
+        );
+        assert!(sarif.contains(r#""uri":"dir/my%20file.bf""#), "{}", sarif);
+        // The ']' is at byte column 6, but the 'é' is a single UTF-16 unit.
+        assert!(
+            sarif.contains(r#""startColumn":5,"byteOffset":5,"endLine":1,"endColumn":6"#),
+            "{}",
+            sarif
+        );
+
+        // Columns are left out for files whose content is unknown.
+        assert!(!list.to_sarif(&[]).contains("Column\""));
+    }
+}
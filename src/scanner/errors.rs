@@ -1,17 +1,98 @@
+use crate::scanner::{Code, Edit, Severity, Suggestion};
 use crate::token::Position;
 use std::cmp::Ordering;
 use std::error::Error as StdError;
 use std::fmt;
+use std::ops::Index;
 
+/// A diagnostic at a position of the source.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Error {
+    /// Position of the start of the offending source.
     pub pos: Position,
+    /// Position just past the offending source range. It is invalid if the
+    /// error only has a start position.
+    pub end: Position,
+    /// How serious the error is.
+    pub severity: Severity,
+    /// The code of the error, if it has one.
+    pub code: Option<Code>,
+    /// The message of the error.
     pub msg: String,
+    /// Secondary source ranges that help explain the error.
+    pub labels: Vec<Label>,
+    /// Fixes for the error, the most likely first.
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// A secondary source range attached to an [`Error`], such as the loop an
+/// error refers to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Label {
+    /// Position of the start of the range.
+    pub pos: Position,
+    /// Position just past the range.
+    pub end: Position,
+    /// What the range is.
+    pub msg: String,
+}
+
+impl Error {
+    /// Returns an error at pos with severity Error and no code.
+    pub fn new(pos: Position, msg: impl Into<String>) -> Self {
+        Self {
+            pos,
+            end: Position::default(),
+            severity: Severity::Error,
+            code: None,
+            msg: msg.into(),
+            labels: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Sets the code of the error along with the code's default severity.
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self.severity = code.default_severity();
+        self
+    }
+
+    /// Sets the severity of the error.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Sets the end of the offending source range.
+    pub fn with_end(mut self, end: Position) -> Self {
+        self.end = end;
+        self
+    }
+
+    /// Adds a label for the range from pos to end.
+    pub fn with_label(mut self, pos: Position, end: Position, msg: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            pos,
+            end,
+            msg: msg.into(),
+        });
+        self
+    }
+
+    /// Adds a suggestion made of edits.
+    pub fn with_suggestion(mut self, msg: impl Into<String>, edits: Vec<Edit>) -> Self {
+        self.suggestions.push(Suggestion {
+            msg: msg.into(),
+            edits,
+        });
+        self
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.pos.is_valid() {
+        if self.pos.is_valid() || !self.pos.filename.is_empty() {
             write!(f, "{}: {}", self.pos, self.msg)
         } else {
             write!(f, "{}", self.msg)
@@ -21,54 +102,114 @@ impl fmt::Display for Error {
 
 impl StdError for Error {}
 
+/// A list of errors.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ErrorList(Vec<Error>);
 
 impl ErrorList {
+    /// Returns an empty list.
     pub fn new() -> Self {
         Self(Vec::new())
     }
 
+    /// Appends an error at pos.
     pub fn add(&mut self, pos: Position, msg: impl Into<String>) {
-        self.0.push(Error {
-            pos,
-            msg: msg.into(),
-        });
+        self.0.push(Error::new(pos, msg));
+    }
+
+    /// Appends err.
+    pub fn push(&mut self, err: Error) {
+        self.0.push(err);
     }
 
+    /// Moves the errors of other, for example those of another file, to the
+    /// end of the list. Call sort afterwards to order them by position.
+    pub fn merge(&mut self, mut other: ErrorList) {
+        self.0.append(&mut other.0);
+    }
+
+    /// Removes all errors.
     pub fn reset(&mut self) {
         self.0.clear();
     }
 
+    /// Sorts the errors by filename, line, column and message.
     pub fn sort(&mut self) {
-        self.0.sort_by(|a, b| match a.pos.line.cmp(&b.pos.line) {
-            Ordering::Equal => match a.pos.column.cmp(&b.pos.column) {
-                Ordering::Equal => a.msg.cmp(&b.msg),
+        self.0
+            .sort_by(|a, b| match a.pos.filename.cmp(&b.pos.filename) {
+                Ordering::Equal => match a.pos.line.cmp(&b.pos.line) {
+                    Ordering::Equal => match a.pos.column.cmp(&b.pos.column) {
+                        Ordering::Equal => a.msg.cmp(&b.msg),
+                        other => other,
+                    },
+                    other => other,
+                },
                 other => other,
-            },
-            other => other,
-        });
+            });
     }
 
+    /// Sorts the list and keeps only the first error of each line.
     pub fn remove_multiples(&mut self) {
         self.sort();
         let mut unique = Vec::new();
         let mut last_line = None;
         for err in &self.0 {
-            if Some(err.pos.line) != last_line {
+            let line = (&err.pos.filename, err.pos.line);
+            if Some(line) != last_line {
                 unique.push(err.clone());
-                last_line = Some(err.pos.line);
+                last_line = Some(line);
             }
         }
         self.0 = unique;
     }
 
+    /// Removes all errors that sit at the same position as the error
+    /// before them, keeping the first. Unlike remove_multiples, errors on
+    /// the same line at different columns are all kept.
+    pub fn remove_multiples_by_pos(&mut self) {
+        self.sort();
+        self.0.dedup_by(|b, a| a.pos == b.pos);
+    }
+
+    /// Returns the number of errors.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns an iterator over the errors.
+    pub fn iter(&self) -> std::slice::Iter<'_, Error> {
+        self.0.iter()
+    }
+
+    /// Returns the error at index i, if any.
+    pub fn get(&self, i: usize) -> Option<&Error> {
+        self.0.get(i)
+    }
+
+    /// Keeps only the first len errors.
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
+    }
+
+    /// Keeps only the errors for which f returns true.
+    pub fn retain_mut(&mut self, f: impl FnMut(&mut Error) -> bool) {
+        self.0.retain_mut(f);
+    }
+
+    /// Reports whether the list contains a diagnostic of error severity.
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|err| err.severity == Severity::Error)
+    }
+
+    /// Reports whether the list has no errors.
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
+    /// Returns the list if it contains errors; warnings and notes alone do
+    /// not make it fail.
     pub fn err(&self) -> Option<&Self> {
-        if self.is_empty() { None } else { Some(self) }
+        if self.has_errors() { Some(self) } else { None }
     }
 }
 
@@ -83,3 +224,103 @@ impl fmt::Display for ErrorList {
 }
 
 impl StdError for ErrorList {}
+
+impl Default for ErrorList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for ErrorList {
+    type Output = Error;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.0[i]
+    }
+}
+
+impl IntoIterator for ErrorList {
+    type Item = Error;
+    type IntoIter = std::vec::IntoIter<Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ErrorList {
+    type Item = &'a Error;
+    type IntoIter = std::slice::Iter<'a, Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Extend<Error> for ErrorList {
+    fn extend<T: IntoIterator<Item = Error>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl FromIterator<Error> for ErrorList {
+    fn from_iter<T: IntoIterator<Item = Error>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(filename: &str, line: usize, column: usize) -> Position {
+        Position {
+            filename: filename.to_string(),
+            offset: column - 1,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn list() {
+        let mut list: ErrorList = [
+            Error::new(pos("b.bf", 1, 1), "b"),
+            Error::new(pos("a.bf", 2, 1), "c"),
+            Error::new(pos("a.bf", 1, 3), "a"),
+        ]
+        .into_iter()
+        .collect();
+        list.extend([Error::new(pos("a.bf", 1, 3), "d")]);
+        assert_eq!(list.len(), 4);
+
+        list.sort();
+        let msgs: Vec<_> = list.iter().map(|err| err.msg.as_str()).collect();
+        assert_eq!(msgs, ["a", "d", "c", "b"]);
+        assert_eq!(list[2].msg, "c");
+        assert_eq!(list.get(4), None);
+
+        let mut other = ErrorList::new();
+        other.add(pos("a.bf", 2, 4), "e");
+        list.merge(other);
+        list.remove_multiples_by_pos();
+        let msgs: Vec<_> = (&list).into_iter().map(|err| err.msg.clone()).collect();
+        assert_eq!(msgs, ["a", "c", "e", "b"]);
+        list.remove_multiples();
+        let msgs: Vec<_> = list.into_iter().map(|err| err.msg).collect();
+        assert_eq!(msgs, ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn severities() {
+        let mut list = ErrorList::new();
+        assert!(list.err().is_none());
+        list.push(Error::new(pos("", 1, 1), "w").with_severity(Severity::Warning));
+        assert!(!list.has_errors());
+        assert!(list.err().is_none());
+        list.push(Error::new(pos("", 1, 2), "e"));
+        assert!(list.err().is_some());
+        list.truncate(1);
+        assert!(list.err().is_none());
+    }
+}
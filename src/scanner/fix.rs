@@ -0,0 +1,213 @@
+use crate::scanner::ErrorList;
+use std::ops::Range;
+
+/// Replaces the bytes in `range` of a file with `replacement`. Offsets are
+/// byte offsets into the file of the diagnostic the edit belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The bytes to replace.
+    pub range: Range<usize>,
+    /// The text that replaces them.
+    pub replacement: String,
+}
+
+impl Edit {
+    /// Returns an edit that inserts text at offset.
+    pub fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self {
+            range: offset..offset,
+            replacement: text.into(),
+        }
+    }
+
+    /// Returns an edit that deletes range.
+    pub fn delete(range: Range<usize>) -> Self {
+        Self {
+            range,
+            replacement: String::new(),
+        }
+    }
+}
+
+/// A fix for a diagnostic. Its edits can be applied without review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// What the fix does.
+    pub msg: String,
+    /// The edits that make up the fix.
+    pub edits: Vec<Edit>,
+}
+
+impl ErrorList {
+    /// Applies the first suggestion of every diagnostic in filename to src,
+    /// which must be the content the diagnostics were reported for.
+    /// Suggestions whose edits overlap an edit that was already accepted,
+    /// or each other, are skipped as a whole, so the result never contains
+    /// half a fix. Returns the new content and the number of suggestions
+    /// applied.
+    pub fn apply_fixes(&self, filename: &str, src: &[u8]) -> (Vec<u8>, usize) {
+        let mut suggestions: Vec<&Suggestion> = self
+            .iter()
+            .filter(|err| err.pos.filename == filename)
+            .filter_map(|err| err.suggestions.first())
+            .filter(|s| !s.edits.is_empty())
+            .collect();
+        suggestions.sort_by_key(|s| s.edits.iter().map(|e| e.range.start).min());
+
+        let mut accepted: Vec<&Edit> = Vec::new();
+        let mut applied = 0;
+        for suggestion in suggestions {
+            let conflicts = suggestion.edits.iter().enumerate().any(|(i, edit)| {
+                edit.range.start > edit.range.end
+                    || edit.range.end > src.len()
+                    || accepted
+                        .iter()
+                        .copied()
+                        .chain(&suggestion.edits[..i])
+                        .any(|other| overlaps(&edit.range, &other.range))
+            });
+            if !conflicts {
+                accepted.extend(&suggestion.edits);
+                applied += 1;
+            }
+        }
+
+        accepted.sort_by_key(|edit| (edit.range.start, edit.range.end));
+        let mut out = Vec::with_capacity(src.len());
+        let mut last = 0;
+        for edit in accepted {
+            out.extend_from_slice(&src[last..edit.range.start]);
+            out.extend_from_slice(edit.replacement.as_bytes());
+            last = edit.range.end;
+        }
+        out.extend_from_slice(&src[last..]);
+        (out, applied)
+    }
+}
+
+// Two edits overlap if they replace common bytes, or if one inserts text
+// strictly inside the range the other replaces. Insertions at the same
+// offset do not overlap; they are applied in order.
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    if a.is_empty() || b.is_empty() {
+        (b.start < a.start && a.start < b.end) || (a.start < b.start && b.start < a.end)
+    } else {
+        a.start < b.end && b.start < a.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_file;
+    use crate::scanner::{Error, Mode};
+    use crate::token::{FileSet, Position};
+
+    #[test]
+    fn fixes() {
+        let src = "+]+[-";
+        let fset = FileSet::new();
+        let err = parse_file(&fset, "a.bf", src, Mode::default()).unwrap_err();
+        let list = err.downcast_ref::<ErrorList>().unwrap();
+        let (fixed, applied) = list.apply_fixes("a.bf", src.as_bytes());
+        assert_eq!(applied, 2);
+        assert_eq!(fixed, b"++[-]");
+
+        // Only fixes for the given file are applied.
+        assert_eq!(list.apply_fixes("b.bf", src.as_bytes()).1, 0);
+    }
+
+    // Returns a list of errors in a.bf, each with a single suggestion made
+    // of the given edits.
+    fn suggestions(fixes: &[Vec<Edit>]) -> ErrorList {
+        let mut list = ErrorList::new();
+        for edits in fixes {
+            let offset = edits[0].range.start;
+            let pos = Position {
+                filename: "a.bf".to_string(),
+                offset,
+                line: 1,
+                column: offset + 1,
+            };
+            list.push(Error::new(pos, "error").with_suggestion("fix", edits.clone()));
+        }
+        list
+    }
+
+    fn apply(fixes: &[Vec<Edit>], src: &str) -> (String, usize) {
+        let (out, applied) = suggestions(fixes).apply_fixes("a.bf", src.as_bytes());
+        (String::from_utf8(out).unwrap(), applied)
+    }
+
+    fn replace(range: Range<usize>, text: &str) -> Edit {
+        Edit {
+            range,
+            replacement: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn overlapping_fixes() {
+        // The second deletion overlaps the first, so it is skipped.
+        let fixes = [vec![Edit::delete(1..3)], vec![Edit::delete(2..4)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("adef".to_string(), 1));
+
+        // A suggestion is skipped as a whole if any of its edits overlaps.
+        let fixes = [
+            vec![Edit::delete(2..3)],
+            vec![Edit::insert(5, ">"), Edit::delete(2..4)],
+        ];
+        assert_eq!(apply(&fixes, "abcdef"), ("abdef".to_string(), 1));
+
+        // An insertion strictly inside a replaced range overlaps it.
+        let fixes = [vec![replace(1..4, "X")], vec![Edit::insert(2, "!")]];
+        assert_eq!(apply(&fixes, "abcdef"), ("aXef".to_string(), 1));
+
+        // Edits of one suggestion that overlap each other are not applied.
+        let fixes = [vec![Edit::delete(0..3), Edit::delete(1..2)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("abcdef".to_string(), 0));
+
+        // Neither are edits outside the content or with a backwards range.
+        let fixes = [vec![Edit::delete(5..9)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("abcdef".to_string(), 0));
+        #[allow(clippy::reversed_empty_ranges)]
+        let fixes = [vec![Edit::delete(3..1)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("abcdef".to_string(), 0));
+    }
+
+    #[test]
+    fn adjacent_fixes() {
+        // Deletions that touch do not overlap.
+        let fixes = [vec![Edit::delete(1..2)], vec![Edit::delete(2..3)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("adef".to_string(), 2));
+
+        // Nor do insertions at the ends of a replaced range.
+        let fixes = [
+            vec![replace(2..4, "X")],
+            vec![Edit::insert(2, "<")],
+            vec![Edit::insert(4, ">")],
+        ];
+        assert_eq!(apply(&fixes, "abcdef"), ("ab<X>ef".to_string(), 3));
+
+        // Insertions at the same offset are applied in order.
+        let fixes = [vec![Edit::insert(3, "1")], vec![Edit::insert(3, "2")]];
+        assert_eq!(apply(&fixes, "abcdef"), ("abc12def".to_string(), 2));
+    }
+
+    #[test]
+    fn unordered_fixes() {
+        // Errors are not in source order, and neither are the edits of the
+        // last suggestion.
+        let fixes = [
+            vec![Edit::delete(5..6)],
+            vec![Edit::insert(0, "[")],
+            vec![Edit::insert(4, ")"), Edit::insert(2, "(")],
+        ];
+        assert_eq!(apply(&fixes, "abcdef"), ("[ab(cd)e".to_string(), 3));
+
+        // Overlaps are resolved in source order, whatever the order of the
+        // errors.
+        let fixes = [vec![Edit::delete(3..5)], vec![Edit::delete(1..4)]];
+        assert_eq!(apply(&fixes, "abcdef"), ("aef".to_string(), 1));
+    }
+}
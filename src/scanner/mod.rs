@@ -1,5 +1,18 @@
+//! Splitting of source into tokens, and the diagnostics that report
+//! problems in it.
+
+mod codes;
+mod emit;
 mod errors;
+mod fix;
+mod render;
+#[allow(clippy::module_inception)]
 mod scanner;
+mod tokens;
 
+pub use codes::*;
 pub use errors::*;
+pub use fix::*;
+pub use render::*;
 pub use scanner::*;
+pub use tokens::*;
@@ -0,0 +1,301 @@
+use crate::scanner::{Error, ErrorList, Severity};
+use crate::token::Position;
+use std::fmt::{self, Write};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders diagnostics together with the source lines they point at:
+///
+/// ```text
+/// error[BF0002]: unmatched '['
+///  --> hello.bf:1:3
+///   |
+/// 1 | ++[->+<
+///   |   ^
+///   |        - expected ']' before the end of the input
+/// ```
+///
+/// The primary range of an error is underlined with `^`, its labels with
+/// `-`. Errors in files that were not added to the renderer are printed
+/// without a snippet.
+pub struct Renderer<'a> {
+    files: Vec<(&'a str, &'a [u8])>,
+    color: bool,
+}
+
+// An underlined range within a single line.
+struct Mark<'e> {
+    line: usize,
+    line_start: usize,
+    line_end: usize,
+    start: usize,
+    end: usize,
+    primary: bool,
+    msg: &'e str,
+}
+
+impl<'a> Renderer<'a> {
+    /// Returns a renderer without any files.
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            color: false,
+        }
+    }
+
+    /// Registers the content of filename so snippets can be shown for it.
+    pub fn add_file(&mut self, filename: &'a str, content: &'a [u8]) {
+        self.files.push((filename, content));
+    }
+
+    /// Enables or disables ANSI color escapes in the output.
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    fn content(&self, filename: &str) -> Option<&'a [u8]> {
+        self.files
+            .iter()
+            .find(|(name, _)| *name == filename)
+            .map(|&(_, content)| content)
+    }
+
+    fn paint(&self, w: &mut dyn Write, style: &str, s: &str) -> fmt::Result {
+        if self.color {
+            write!(w, "{}{}{}", style, s, RESET)
+        } else {
+            w.write_str(s)
+        }
+    }
+
+    fn mark<'e>(
+        content: &[u8],
+        pos: &Position,
+        end: &Position,
+        primary: bool,
+        msg: &'e str,
+    ) -> Mark<'e> {
+        let line_start = pos.offset + 1 - pos.column;
+        let mut line_end = content[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(content.len(), |i| line_start + i);
+        if line_end > line_start && content[line_end - 1] == b'\r' {
+            line_end -= 1;
+        }
+
+        // Ranges are cut at the end of their first line; a range without an
+        // end covers the character at pos.
+        let start = pos.offset.min(line_end);
+        let end = if end.is_valid() && end.filename == pos.filename && end.offset > start {
+            end.offset.min(line_end)
+        } else {
+            content[start..line_end]
+                .iter()
+                .skip(1)
+                .position(|&b| b & 0xC0 != 0x80)
+                .map_or(line_end, |i| start + i + 1)
+        };
+
+        Mark {
+            line: pos.line,
+            line_start,
+            line_end,
+            start,
+            end,
+            primary,
+            msg,
+        }
+    }
+
+    /// Writes err to w.
+    pub fn write(&self, w: &mut dyn Write, err: &Error) -> fmt::Result {
+        let color = match err.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+        match err.code {
+            Some(code) => self.paint(w, color, &format!("{}[{}]", err.severity, code))?,
+            None => self.paint(w, color, &err.severity.to_string())?,
+        }
+        self.paint(w, BOLD, &format!(": {}", err.msg))?;
+        writeln!(w)?;
+
+        let gutter = if err.pos.is_valid() {
+            self.write_snippet(w, err, color)?
+        } else {
+            if !err.pos.filename.is_empty() {
+                writeln!(w, " --> {}", err.pos.filename)?;
+            }
+            " ".to_string()
+        };
+
+        for suggestion in &err.suggestions {
+            self.paint(w, BLUE, &format!("{} =", gutter))?;
+            writeln!(w, " help: {}", suggestion.msg)?;
+        }
+
+        Ok(())
+    }
+
+    // Writes the location and source lines of err and returns the gutter
+    // used to indent them.
+    fn write_snippet(
+        &self,
+        w: &mut dyn Write,
+        err: &Error,
+        color: &str,
+    ) -> Result<String, fmt::Error> {
+        let content = self.content(&err.pos.filename);
+        let mut marks = Vec::new();
+        if let Some(content) = content {
+            marks.push(Self::mark(content, &err.pos, &err.end, true, ""));
+            for label in &err.labels {
+                if label.pos.is_valid() && label.pos.filename == err.pos.filename {
+                    marks.push(Self::mark(
+                        content, &label.pos, &label.end, false, &label.msg,
+                    ));
+                }
+            }
+        }
+        marks.sort_by_key(|m| m.line);
+
+        let width = marks
+            .last()
+            .map_or(err.pos.line, |m| m.line)
+            .to_string()
+            .len();
+        let gutter = " ".repeat(width);
+        writeln!(w, "{}--> {}", gutter, err.pos)?;
+        let Some(content) = content else {
+            return Ok(gutter);
+        };
+
+        self.paint(w, BLUE, &format!("{} |", gutter))?;
+        writeln!(w)?;
+        let mut last_line = None;
+        for mark in &marks {
+            if last_line != Some(mark.line) {
+                if last_line.is_some_and(|l| l + 1 < mark.line) {
+                    self.paint(w, BLUE, "...")?;
+                    writeln!(w)?;
+                }
+
+                self.paint(w, BLUE, &format!("{:>width$} |", mark.line))?;
+                let text = String::from_utf8_lossy(&content[mark.line_start..mark.line_end]);
+                writeln!(w, " {}", text)?;
+                last_line = Some(mark.line);
+            }
+
+            // Keep tabs in the padding so the underline lines up with the
+            // source line whatever the tab width.
+            let pad: String = String::from_utf8_lossy(&content[mark.line_start..mark.start])
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let len = String::from_utf8_lossy(&content[mark.start..mark.end])
+                .chars()
+                .count()
+                .max(1);
+            let (style, underline) = if mark.primary {
+                (color, "^")
+            } else {
+                (BLUE, "-")
+            };
+
+            self.paint(w, BLUE, &format!("{} |", gutter))?;
+            write!(w, " {}", pad)?;
+            self.paint(w, style, &underline.repeat(len))?;
+            if !mark.msg.is_empty() {
+                w.write_char(' ')?;
+                self.paint(w, style, mark.msg)?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(gutter)
+    }
+
+    /// Renders err to a string.
+    pub fn render(&self, err: &Error) -> String {
+        let mut s = String::new();
+        self.write(&mut s, err).unwrap();
+        s
+    }
+
+    /// Renders every error of list, separated by blank lines.
+    pub fn render_list(&self, list: &ErrorList) -> String {
+        let mut s = String::new();
+        for (i, err) in list.iter().enumerate() {
+            if i > 0 {
+                s.push('\n');
+            }
+            self.write(&mut s, err).unwrap();
+        }
+        s
+    }
+}
+
+impl Default for Renderer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_file;
+    use crate::scanner::Mode;
+    use crate::token::FileSet;
+
+    fn errors(filename: &str, src: &str) -> ErrorList {
+        let fset = FileSet::new();
+        let err = parse_file(&fset, filename, src, Mode::default()).unwrap_err();
+        err.downcast::<ErrorList>().map(|list| *list).unwrap()
+    }
+
+    #[test]
+    fn render() {
+        let src = "++[->+<";
+        let list = errors("hello.bf", src);
+        let mut renderer = Renderer::new();
+        renderer.add_file("hello.bf", src.as_bytes());
+        assert_eq!(
+            renderer.render_list(&list),
+            concat!(
+                "error[BF0002]: unmatched '['\n",
+                " --> hello.bf:1:3\n",
+                "  |\n",
+                "1 | ++[->+<\n",
+                "  |   ^\n",
+                "  |        - expected ']' before the end of the input\n",
+                "  = help: close the loop at the end of the input\n",
+            )
+        );
+
+        renderer.set_color(true);
+        assert!(renderer.render_list(&list).contains(RED));
+    }
+
+    #[test]
+    fn render_without_content() {
+        let list = errors("a.bf", "+\n\t]");
+        let out = Renderer::new().render_list(&list);
+        assert_eq!(
+            out,
+            "error[BF0001]: unmatched ']'\n --> a.bf:2:2\n  = help: remove the ']'\n"
+        );
+
+        let mut renderer = Renderer::new();
+        renderer.add_file("a.bf", b"+\n\t]");
+        let out = renderer.render_list(&list);
+        assert!(out.contains("2 | \t]\n  | \t^\n"), "{}", out);
+    }
+}
@@ -1,28 +1,98 @@
+use crate::scanner::{Code, Edit, Error};
 use crate::token;
+use std::io::{self, Read};
+use std::ops::BitOr;
 use std::rc::Rc;
 
 const BOM: char = '\u{FEFF}';
 const EOF: char = '\u{FFFF}';
 
+const CHUNK_SIZE: usize = 64 * 1024;
+const UTF8_MAX: usize = 4;
+
+/// Receives the errors a [`Scanner`] finds.
+pub type ErrorHandler = Box<dyn FnMut(Error)>;
+
+/// A set of flags controlling optional scanner features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Mode(u32);
+
+impl Mode {
+    /// Scan `#` as a [`token::Token::DebugDump`] instead of reporting it as illegal.
+    pub const DEBUG_DUMP: Mode = Mode(1 << 0);
+    /// Treat `!` as the end of the program; the remaining bytes are program input.
+    pub const INPUT_SEPARATOR: Mode = Mode(1 << 1);
+    /// Scan runs of non-command characters as [`token::Token::Comment`]
+    /// instead of reporting them as illegal.
+    pub const SCAN_COMMENTS: Mode = Mode(1 << 2);
+
+    /// Reports whether all flags of other are set.
+    pub fn contains(self, other: Mode) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Mode {
+    type Output = Mode;
+
+    fn bitor(self, rhs: Mode) -> Self::Output {
+        Mode(self.0 | rhs.0)
+    }
+}
+
+// Decodes the first character of s, which must not be empty. Invalid
+// encodings decode to U+FFFD with a width of 1.
+fn decode_char(s: &[u8]) -> (char, usize) {
+    let w = match s[0] {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return ('\u{FFFD}', 1),
+    };
+
+    match s.get(..w).and_then(|b| std::str::from_utf8(b).ok()) {
+        Some(valid) => (valid.chars().next().unwrap(), w),
+        None => ('\u{FFFD}', 1),
+    }
+}
+
+/// Splits source into tokens, reading it in chunks.
 pub struct Scanner<'a> {
     source: Rc<token::Source>,
-    src: &'a [u8],
-    eh: Option<Box<dyn FnMut(token::Position, &str)>>,
+    src: Box<dyn Read + 'a>,
+    eh: Option<ErrorHandler>,
+    mode: Mode,
+
+    // input buffer; buf[0] is the byte at offset buf_offset
+    buf: Vec<u8>,
+    buf_offset: usize,
+    src_eof: bool,
+    io_err: Option<io::Error>,
 
     // scanning state
     ch: char,
     offset: usize,
     rd_offset: usize,
     line_offset: usize,
+    // offset just after the last scanned token
+    end: usize,
+    // whether next() reported ch as illegal
+    reported: bool,
+    input: Option<Vec<u8>>,
 
+    /// The number of errors found so far.
     pub error_count: usize,
 }
 
 impl<'a> Scanner<'a> {
+    /// Returns a scanner of src, whose positions belong to source. Errors are
+    /// passed to error_handler.
     pub fn new(
         source: Rc<token::Source>,
         src: &'a [u8],
-        error_handler: Option<Box<dyn FnMut(token::Position, &str)>>,
+        error_handler: Option<ErrorHandler>,
+        mode: Mode,
     ) -> Self {
         if source.size() != src.len() {
             panic!(
@@ -32,14 +102,34 @@ impl<'a> Scanner<'a> {
             );
         }
 
+        Self::from_reader(source, src, error_handler, mode)
+    }
+
+    /// Creates a scanner that pulls its input from src in chunks, so the
+    /// whole input never has to be held in memory. The size of source is
+    /// grown as input is read.
+    pub fn from_reader(
+        source: Rc<token::Source>,
+        src: impl Read + 'a,
+        error_handler: Option<ErrorHandler>,
+        mode: Mode,
+    ) -> Self {
         let mut scanner = Self {
             source,
-            src,
+            src: Box::new(src),
             eh: error_handler,
+            mode,
+            buf: Vec::new(),
+            buf_offset: 0,
+            src_eof: false,
+            io_err: None,
             ch: ' ',
             offset: 0,
             rd_offset: 0,
             line_offset: 0,
+            end: 0,
+            reported: false,
+            input: None,
             error_count: 0,
         };
 
@@ -51,35 +141,74 @@ impl<'a> Scanner<'a> {
         scanner
     }
 
+    fn buf_end(&self) -> usize {
+        self.buf_offset + self.buf.len()
+    }
+
+    // Reads one more chunk from src, or what is left of it if that is
+    // less. Sources that return a few bytes per read are read until the
+    // chunk is full. Read errors are recorded and end the input.
+    fn read_chunk(&mut self) {
+        let mut chunk = self.src.by_ref().take(CHUNK_SIZE as u64);
+        let len = self.buf.len();
+        match chunk.read_to_end(&mut self.buf) {
+            Ok(n) => self.src_eof = n < CHUNK_SIZE,
+            Err(err) => {
+                self.buf.truncate(len);
+                self.io_err = Some(err);
+                self.src_eof = true;
+            }
+        }
+
+        self.source.grow_to(self.buf_end());
+    }
+
+    // Makes sure that a complete UTF-8 sequence starting at rd_offset is
+    // buffered, unless the input ends first. Bytes before rd_offset are
+    // no longer needed and are discarded.
+    fn fill(&mut self) {
+        if self.src_eof || self.rd_offset + UTF8_MAX <= self.buf_end() {
+            return;
+        }
+
+        self.buf.drain(..self.rd_offset - self.buf_offset);
+        self.buf_offset = self.rd_offset;
+        while !self.src_eof && self.buf.len() < UTF8_MAX {
+            self.read_chunk();
+        }
+    }
+
     fn next(&mut self) {
-        if self.rd_offset < self.src.len() {
+        self.fill();
+        if self.rd_offset < self.buf_end() {
             self.offset = self.rd_offset;
             if self.ch == '\n' {
                 self.line_offset = self.offset;
+                self.source.add_line(self.offset);
             }
 
-            let s = &self.src[self.rd_offset..];
-            let (r, w) = match std::str::from_utf8(s) {
-                Ok(valid) => {
-                    let ch = valid.chars().next().unwrap_or(EOF);
-                    let len = ch.len_utf8();
-                    (ch, len)
-                }
-                Err(_) => (EOF, 1),
-            };
+            let (r, w) = decode_char(&self.buf[self.rd_offset - self.buf_offset..]);
 
+            self.reported = true;
             if r == '\0' {
-                self.error(self.offset, "illegal character NUL");
+                self.error(self.offset, w, Code::ILLEGAL_NUL, "illegal character NUL");
             } else if r == '\u{FFFD}' && w == 1 {
-                self.error(self.offset, "illegal UTF-8 encoding");
+                self.error(
+                    self.offset,
+                    w,
+                    Code::ILLEGAL_ENCODING,
+                    "illegal UTF-8 encoding",
+                );
             } else if r == BOM && self.offset > 0 {
-                self.error(self.offset, "illegal byte order mark");
+                self.error(self.offset, w, Code::ILLEGAL_BOM, "illegal byte order mark");
+            } else {
+                self.reported = false;
             }
 
             self.rd_offset += w;
             self.ch = r;
         } else {
-            self.offset = self.src.len();
+            self.offset = self.buf_end();
             if self.ch == '\n' {
                 self.line_offset = self.offset;
             }
@@ -87,39 +216,110 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    fn peek(&self) -> u8 {
-        if self.rd_offset < self.src.len() {
-            self.src[self.rd_offset]
-        } else {
-            0
+    // Reports an error for the width bytes starting at offset.
+    fn error(&mut self, offset: usize, width: usize, code: Code, msg: &str) {
+        if let Some(ref mut handler) = self.eh {
+            let pos = self.source.position(self.source.pos(offset));
+            let end = self.source.position(self.source.pos(offset + width));
+            let err = Error::new(pos, msg)
+                .with_end(end)
+                .with_code(code)
+                .with_suggestion(
+                    "remove the character",
+                    vec![Edit::delete(offset..offset + width)],
+                );
+            handler(err);
         }
+        self.error_count += 1;
     }
 
-    fn error(&mut self, offset: usize, msg: &str) {
-        if let Some(ref mut handler) = self.eh {
-            let pos = self.source.position(token::Pos(offset));
-            handler(pos, msg);
+    fn skip_whitespace(&mut self) {
+        while matches!(self.ch, ' ' | '\t' | '\n' | '\r') {
+            self.next();
         }
-        self.error_count += 1;
     }
 
-    fn errorf(&mut self, offset: usize, msg: &str, args: impl std::fmt::Display) {
-        self.error(offset, format!("{}{}", msg, args).as_str());
+    /// Returns the position just after the last token returned by scan. It
+    /// accounts for the real width of illegal characters.
+    pub fn end(&self) -> token::Pos {
+        self.source.pos(self.end)
     }
 
-    fn skip_whitespace(&mut self) {
-        while matches!(self.ch, ' ' | '\t' | '\n' | '\r') {
+    /// Takes the bytes following the `!` input separator, if one was scanned.
+    pub fn take_input(&mut self) -> Option<Vec<u8>> {
+        self.input.take()
+    }
+
+    /// Takes the error that ended reading the input early, if any.
+    pub fn take_io_error(&mut self) -> Option<io::Error> {
+        self.io_err.take()
+    }
+
+    fn stop_at_input(&mut self) {
+        // Everything after '!' is raw input data, so it must not be decoded
+        // (and possibly reported as illegal) by next().
+        self.buf.drain(..self.rd_offset - self.buf_offset);
+        self.buf_offset = self.rd_offset;
+        while !self.src_eof {
+            self.read_chunk();
+        }
+
+        self.input = Some(std::mem::take(&mut self.buf));
+        self.buf_offset += self.input.as_ref().map_or(0, Vec::len);
+        self.offset = self.buf_offset;
+        self.rd_offset = self.buf_offset;
+        self.ch = EOF;
+    }
+
+    fn is_command(&self, ch: char) -> bool {
+        match ch {
+            '+' | '-' | '>' | '<' | '[' | ']' | '.' | ',' => true,
+            '#' => self.mode.contains(Mode::DEBUG_DUMP),
+            '!' => self.mode.contains(Mode::INPUT_SEPARATOR),
+            _ => false,
+        }
+    }
+
+    // Scans the rest of a comment whose first character ch has already
+    // been consumed. A comment runs up to the end of the line or the next
+    // command; trailing whitespace is not part of it.
+    fn scan_comment(&mut self, ch: char) -> String {
+        let mut lit = String::from(ch);
+        let mut spaces = String::new();
+        while self.ch != '\n' && self.ch != EOF && !self.is_command(self.ch) {
+            let blank = matches!(self.ch, ' ' | '\t' | '\r');
+            if blank {
+                spaces.push(self.ch);
+            } else {
+                lit.push_str(&spaces);
+                spaces.clear();
+                lit.push(self.ch);
+            }
             self.next();
+            if !blank {
+                self.end = self.offset;
+            }
         }
+        lit
     }
 
+    /// Returns the position, token and literal text of the next token.
     pub fn scan(&mut self) -> (token::Pos, token::Token, String) {
         self.skip_whitespace();
 
-        let pos = token::Pos(self.offset);
+        let offset = self.offset;
+        let pos = self.source.pos(offset);
         let ch = self.ch;
+        let reported = self.reported;
+
+        if ch == '!' && self.mode.contains(Mode::INPUT_SEPARATOR) {
+            self.end = offset;
+            self.stop_at_input();
+            return (pos, token::Token::EOF, String::new());
+        }
 
         self.next();
+        self.end = self.offset;
 
         let token = match ch {
             EOF => token::Token::EOF,
@@ -131,9 +331,17 @@ impl<'a> Scanner<'a> {
             ']' => token::Token::LoopClose,
             '.' => token::Token::OutputByte,
             ',' => token::Token::InputByte,
+            '#' if self.mode.contains(Mode::DEBUG_DUMP) => token::Token::DebugDump,
+            _ if self.mode.contains(Mode::SCAN_COMMENTS) => {
+                return (pos, token::Token::Comment, self.scan_comment(ch));
+            }
             _ => {
-                if ch != BOM {
-                    self.errorf(self.offset, "illegal character ", format!("{:?}", ch));
+                // NUL, invalid encodings and misplaced byte order marks
+                // have already been reported by next().
+                if !reported {
+                    let width = self.offset - offset;
+                    let msg = format!("illegal character {:?}", ch);
+                    self.error(offset, width, Code::ILLEGAL_CHARACTER, &msg);
                 }
                 return (pos, token::Token::ILLEGAL, ch.to_string());
             }
@@ -142,3 +350,55 @@ impl<'a> Scanner<'a> {
         (pos, token, String::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::Token;
+
+    // A reader that returns a single byte per read.
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((&b, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = b;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        // Puts a two-byte character across the end of the first chunk and
+        // around it.
+        for offset in CHUNK_SIZE - 3..=CHUNK_SIZE + 1 {
+            let src = format!("{}é+", "+".repeat(offset));
+            let readers: [Box<dyn Read>; 2] = [
+                Box::new(src.as_bytes()),
+                Box::new(ByteReader(src.as_bytes())),
+            ];
+            for reader in readers {
+                let source = Rc::new(token::Source::new("", 1, 0));
+                let mut s = Scanner::from_reader(source.clone(), reader, None, Mode::default());
+                for _ in 0..offset {
+                    assert_eq!(s.scan().1, Token::IncByte);
+                }
+                let illegal = (source.pos(offset), Token::ILLEGAL, "é".to_string());
+                assert_eq!(s.scan(), illegal);
+                assert_eq!(s.end(), source.pos(offset + 2));
+                assert_eq!(
+                    s.scan(),
+                    (source.pos(offset + 2), Token::IncByte, String::new())
+                );
+                assert_eq!(s.scan().1, Token::EOF);
+                assert_eq!(s.error_count, 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::scanner::{Error, ErrorHandler, Mode, Scanner};
+use crate::token::{Source, Token};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::rc::Rc;
+
+const BOM: &[u8] = "\u{FEFF}".as_bytes();
+
+/// The kind of a [`Lexeme`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexemeKind {
+    /// A token.
+    Token(Token),
+    /// A run of whitespace.
+    Whitespace,
+    /// A comment.
+    Comment,
+    /// A byte order mark at the start of the input.
+    ByteOrderMark,
+    /// The input data following the `!` separator.
+    Input,
+}
+
+/// A piece of the input. Lexemes cover the input without gaps, so
+/// concatenating their spans reproduces it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme {
+    /// What the lexeme is.
+    pub kind: LexemeKind,
+    /// Byte offsets of the lexeme in the input.
+    pub span: Range<usize>,
+}
+
+/// An iterator over the lexemes of an input, ending with the EOF token and
+/// the input data, if any. Lexical errors are yielded as items before the
+/// lexeme they occur in.
+pub struct Lexemes<'a> {
+    scanner: Scanner<'a>,
+    src: &'a [u8],
+    source: Rc<Source>,
+    errors: Rc<RefCell<Vec<Error>>>,
+
+    // start offset and token of the next scanned token, needed to find the
+    // end of a comment
+    peeked: Option<(usize, Token)>,
+    last_end: usize,
+    queue: VecDeque<Result<Lexeme, Error>>,
+    done: bool,
+}
+
+/// Returns the lexemes of src, scanned with the default mode.
+pub fn tokenize(src: &[u8]) -> Lexemes<'_> {
+    tokenize_with_mode(src, Mode::default())
+}
+
+/// Returns the lexemes of src, scanned with mode.
+pub fn tokenize_with_mode(src: &[u8], mode: Mode) -> Lexemes<'_> {
+    Lexemes::new(Rc::new(Source::new("", 1, src.len())), src, mode)
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+// Returns the width of the character at the start of s the way the
+// scanner decodes it: an invalid encoding is a single byte wide.
+fn char_width(s: &[u8]) -> usize {
+    let chunk = s.utf8_chunks().next().unwrap();
+    chunk.valid().chars().next().map_or(1, char::len_utf8)
+}
+
+impl<'a> Lexemes<'a> {
+    /// Creates an iterator over the lexemes of src, whose size source must
+    /// have. Errors are reported with positions in source.
+    pub fn new(source: Rc<Source>, src: &'a [u8], mode: Mode) -> Self {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let handler: ErrorHandler = {
+            let errors = errors.clone();
+            Box::new(move |err: Error| errors.borrow_mut().push(err))
+        };
+
+        Self {
+            scanner: Scanner::new(source.clone(), src, Some(handler), mode),
+            src,
+            source,
+            errors,
+            peeked: None,
+            last_end: 0,
+            queue: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn scan(&mut self) -> (usize, Token) {
+        let (pos, tok, _) = self.scanner.scan();
+        (self.source.offset(pos), tok)
+    }
+
+    // Queues the next token together with the trivia and errors before it.
+    fn advance(&mut self) {
+        let (start, tok) = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.scan(),
+        };
+        if tok == Token::EOF {
+            self.done = true;
+        } else {
+            self.peeked = Some(self.scan());
+        }
+
+        let mut lexemes = Vec::new();
+        let mut gap = self.last_end;
+        if gap == 0 && self.src.starts_with(BOM) {
+            gap = BOM.len();
+            lexemes.push(Lexeme {
+                kind: LexemeKind::ByteOrderMark,
+                span: 0..gap,
+            });
+        }
+        if gap < start {
+            lexemes.push(Lexeme {
+                kind: LexemeKind::Whitespace,
+                span: gap..start,
+            });
+        }
+
+        let end = match tok {
+            // A comment ends before the whitespace preceding the next token.
+            Token::Comment => {
+                let next = self.peeked.map_or(self.src.len(), |(next, _)| next);
+                let text = &self.src[start..next];
+                start + text.iter().rposition(|&b| !is_space(b)).unwrap() + 1
+            }
+            Token::ILLEGAL => start + char_width(&self.src[start..]),
+            Token::EOF => start + usize::from(self.src.get(start) == Some(&b'!')),
+            _ => start + 1,
+        };
+        let kind = match tok {
+            Token::Comment => LexemeKind::Comment,
+            tok => LexemeKind::Token(tok),
+        };
+        lexemes.push(Lexeme {
+            kind,
+            span: start..end,
+        });
+        if self.done && end < self.src.len() {
+            lexemes.push(Lexeme {
+                kind: LexemeKind::Input,
+                span: end..self.src.len(),
+            });
+        }
+        self.last_end = end;
+
+        let mut errors = self.errors.borrow_mut();
+        errors.sort_by_key(|err| err.pos.offset);
+        for lexeme in lexemes {
+            // Errors found while looking ahead wait for their lexeme.
+            let n = if self.done {
+                errors.len()
+            } else {
+                errors.partition_point(|err| err.pos.offset < lexeme.span.end)
+            };
+            self.queue.extend(errors.drain(..n).map(Err));
+            self.queue.push_back(Ok(lexeme));
+        }
+    }
+}
+
+impl Iterator for Lexemes<'_> {
+    type Item = Result<Lexeme, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.queue.is_empty() && !self.done {
+            self.advance();
+        }
+        self.queue.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Code;
+
+    fn kinds(src: &[u8], mode: Mode) -> Vec<LexemeKind> {
+        let lexemes: Result<Vec<_>, _> = tokenize_with_mode(src, mode).collect();
+        lexemes.unwrap().into_iter().map(|l| l.kind).collect()
+    }
+
+    #[test]
+    fn lexemes() {
+        let src = b"+ [-]\n";
+        let lexemes: Vec<_> = tokenize(src).collect::<Result<_, _>>().unwrap();
+        let text: Vec<u8> = lexemes
+            .iter()
+            .flat_map(|l| src[l.span.clone()].to_vec())
+            .collect();
+        assert_eq!(text, src);
+
+        assert_eq!(
+            kinds(src, Mode::default()),
+            [
+                LexemeKind::Token(Token::IncByte),
+                LexemeKind::Whitespace,
+                LexemeKind::Token(Token::LoopOpen),
+                LexemeKind::Token(Token::DecByte),
+                LexemeKind::Token(Token::LoopClose),
+                LexemeKind::Whitespace,
+                LexemeKind::Token(Token::EOF),
+            ]
+        );
+        let mode = Mode::SCAN_COMMENTS | Mode::INPUT_SEPARATOR;
+        assert_eq!(
+            kinds(b"\xef\xbb\xbf+ a b\n!xy", mode),
+            [
+                LexemeKind::ByteOrderMark,
+                LexemeKind::Token(Token::IncByte),
+                LexemeKind::Whitespace,
+                LexemeKind::Comment,
+                LexemeKind::Whitespace,
+                LexemeKind::Token(Token::EOF),
+                LexemeKind::Input,
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let items: Vec<_> = tokenize(b"+x").collect();
+        let Err(err) = &items[1] else {
+            panic!("expected an error, found {:?}", items[1]);
+        };
+        assert_eq!(err.code, Some(Code::ILLEGAL_CHARACTER));
+        assert_eq!(err.pos.offset, 1);
+        assert!(matches!(
+            &items[2],
+            Ok(Lexeme {
+                kind: LexemeKind::Token(Token::ILLEGAL),
+                span,
+            }) if *span == (1..2)
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::scanner::{Code, Edit, Error, ErrorList, LexemeKind, Lexemes, Mode};
+use crate::syntax::{SyntaxLoop, SyntaxNode, SyntaxToken, SyntaxTree, Trivia, TriviaKind};
+use crate::token::{self, Token};
+use std::mem;
+use std::rc::Rc;
+
+/// A '[' whose closing ']' has not been reached yet.
+struct OpenLoop {
+    open: SyntaxToken,
+    // nodes of the enclosing list that precede the loop
+    outer: Vec<SyntaxNode>,
+}
+
+/// Parses src into a lossless syntax tree. The tree keeps whitespace,
+/// comments and a leading byte order mark as trivia. Without
+/// [`Mode::SCAN_COMMENTS`] comment characters are reported as illegal and
+/// end up as error nodes; either way the tree reproduces src exactly.
+pub fn parse(source: Rc<token::Source>, src: &[u8], mode: Mode) -> (SyntaxTree, ErrorList) {
+    let mut errors = ErrorList::new();
+    let (mut tokens, input) = tokenize(&source, src, mode, &mut errors);
+    attach_trailing_trivia(&mut tokens);
+
+    let mut tokens = tokens.into_iter();
+    let mut open_loops: Vec<OpenLoop> = Vec::new();
+    let mut list = Vec::new();
+    let eof = loop {
+        let tok = tokens.next().unwrap();
+        match tok.kind {
+            Token::LoopOpen => open_loops.push(OpenLoop {
+                open: tok,
+                outer: mem::take(&mut list),
+            }),
+            Token::LoopClose => match open_loops.pop() {
+                Some(open) => close_loop(open, Some(tok), &mut list),
+                None => {
+                    let offset = source.offset(tok.pos);
+                    let err = Error::new(source.position(tok.pos), "unmatched ']'")
+                        .with_end(source.position(tok.end()))
+                        .with_code(Code::UNMATCHED_CLOSE)
+                        .with_suggestion("remove the ']'", vec![Edit::delete(offset..offset + 1)]);
+                    errors.push(err);
+                    list.push(SyntaxNode::Error(tok));
+                }
+            },
+            // The scanner has already reported the illegal character.
+            Token::ILLEGAL => list.push(SyntaxNode::Error(tok)),
+            Token::EOF => break tok,
+            _ => list.push(SyntaxNode::Command(tok)),
+        }
+    };
+
+    while let Some(open) = open_loops.pop() {
+        let err = Error::new(source.position(open.open.pos), "unmatched '['")
+            .with_end(source.position(open.open.end()))
+            .with_code(Code::UNMATCHED_OPEN)
+            .with_label(
+                source.position(eof.pos),
+                Default::default(),
+                "expected ']' before the end of the input",
+            )
+            .with_suggestion(
+                "close the loop at the end of the input",
+                vec![Edit::insert(source.offset(eof.pos), "]")],
+            );
+        errors.push(err);
+        close_loop(open, None, &mut list);
+    }
+    errors.sort();
+
+    let tree = SyntaxTree {
+        nodes: list,
+        eof,
+        input,
+    };
+    (tree, errors)
+}
+
+fn close_loop(open: OpenLoop, close: Option<SyntaxToken>, list: &mut Vec<SyntaxNode>) {
+    let body = mem::replace(list, open.outer);
+    list.push(SyntaxNode::Loop(SyntaxLoop {
+        open: open.open,
+        body,
+        close,
+    }));
+}
+
+// Scans src into a flat list of tokens ending with EOF, and the input
+// data. Comments and the bytes between tokens become the leading trivia of
+// the next token.
+fn tokenize(
+    source: &Rc<token::Source>,
+    src: &[u8],
+    mode: Mode,
+    errors: &mut ErrorList,
+) -> (Vec<SyntaxToken>, Vec<u8>) {
+    let mut tokens = Vec::new();
+    let mut leading = Vec::new();
+    let mut input = Vec::new();
+    for item in Lexemes::new(source.clone(), src, mode) {
+        let lexeme = match item {
+            Ok(lexeme) => lexeme,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+
+        let pos = source.pos(lexeme.span.start);
+        let text = src[lexeme.span].to_vec();
+        let kind = match lexeme.kind {
+            LexemeKind::Token(tok) => {
+                tokens.push(SyntaxToken {
+                    kind: tok,
+                    pos,
+                    text,
+                    leading: mem::take(&mut leading),
+                    trailing: Vec::new(),
+                });
+                continue;
+            }
+            LexemeKind::Input => {
+                input = text;
+                continue;
+            }
+            LexemeKind::Whitespace => TriviaKind::Whitespace,
+            LexemeKind::Comment => TriviaKind::Comment,
+            LexemeKind::ByteOrderMark => TriviaKind::ByteOrderMark,
+        };
+        leading.push(Trivia { kind, pos, text });
+    }
+    (tokens, input)
+}
+
+// Moves the trivia that follows a token on the same line from the leading
+// trivia of the next token to the trailing trivia of the token.
+fn attach_trailing_trivia(tokens: &mut [SyntaxToken]) {
+    for i in 1..tokens.len() {
+        let (prev, next) = tokens.split_at_mut(i);
+        let (prev, next) = (&mut prev[i - 1], &mut next[0]);
+
+        let mut leading = mem::take(&mut next.leading).into_iter();
+        for mut trivia in leading.by_ref() {
+            let newline = match trivia.kind {
+                TriviaKind::Whitespace => trivia.text.iter().position(|&b| b == b'\n'),
+                _ => None,
+            };
+            let Some(newline) = newline else {
+                prev.trailing.push(trivia);
+                continue;
+            };
+
+            if newline > 0 {
+                let rest = trivia.text.split_off(newline);
+                let pos = trivia.pos + newline;
+                prev.trailing.push(trivia);
+                trivia = Trivia {
+                    kind: TriviaKind::Whitespace,
+                    pos,
+                    text: rest,
+                };
+            }
+            next.leading.push(trivia);
+            break;
+        }
+        next.leading.extend(leading);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    use crate::parser::Parser;
+
+    #[test]
+    fn syntax_tree() {
+        let cases: [(&[u8], Mode); 8] = [
+            (b" +[ - ] # comment\n", Mode::SCAN_COMMENTS),
+            (b"+ a\xffb  \n", Mode::SCAN_COMMENTS),
+            (b"\xef\xbb\xbf+[[-]", Mode::default()),
+            (b"]+x\0.", Mode::default()),
+            ("é+".as_bytes(), Mode::default()),
+            (b"+[,]\n!data", Mode::INPUT_SEPARATOR),
+            (b"+#\n\n", Mode::DEBUG_DUMP),
+            (b"", Mode::default()),
+        ];
+        for (src, mode) in cases {
+            let source = Rc::new(token::Source::new("", 1, src.len()));
+            let (tree, errors) = parse(source.clone(), src, mode);
+            assert_eq!(tree.to_bytes(), src, "{:?}", src);
+
+            let mut parser = Parser::new(source, src, mode);
+            let program = ast::Node::Program(parser.parse_program());
+            assert_eq!(format!("{:?}", tree.to_ast()), format!("{:?}", program));
+            assert_eq!(errors.to_string(), parser.errors().to_string());
+        }
+    }
+}
@@ -0,0 +1,7 @@
+//! A lossless syntax tree that keeps whitespace and comments.
+
+mod build;
+mod tree;
+
+pub use build::*;
+pub use tree::*;
@@ -0,0 +1,270 @@
+use crate::ast::{self, Spanned};
+use crate::token::{self, Token};
+use std::slice;
+
+/// The kind of a [`Trivia`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of whitespace.
+    Whitespace,
+    /// A comment.
+    Comment,
+    /// A byte order mark at the start of the file.
+    ByteOrderMark,
+}
+
+/// Source text that has no meaning to the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    /// What the trivia is.
+    pub kind: TriviaKind,
+    /// Position of the first byte.
+    pub pos: token::Pos,
+    /// The bytes of the trivia.
+    pub text: Vec<u8>,
+}
+
+/// A token together with the exact bytes it was scanned from. Trailing
+/// trivia runs up to the end of the token's line; all other trivia before
+/// a token is its leading trivia.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    /// The token.
+    pub kind: Token,
+    /// Position of the first byte.
+    pub pos: token::Pos,
+    /// The bytes the token was scanned from.
+    pub text: Vec<u8>,
+    /// Trivia before the token.
+    pub leading: Vec<Trivia>,
+    /// Trivia after the token, up to the end of its line.
+    pub trailing: Vec<Trivia>,
+}
+
+impl SyntaxToken {
+    /// Returns the position just after the token.
+    pub fn end(&self) -> token::Pos {
+        self.pos + self.text.len()
+    }
+}
+
+/// A node of a [`SyntaxTree`].
+#[derive(Debug)]
+pub enum SyntaxNode {
+    /// A command.
+    Command(SyntaxToken),
+    /// A loop.
+    Loop(SyntaxLoop),
+    /// An illegal character or unmatched ']'.
+    Error(SyntaxToken),
+}
+
+impl SyntaxNode {
+    /// Returns the first token of the node.
+    pub fn first_token(&self) -> &SyntaxToken {
+        match self {
+            SyntaxNode::Command(t) | SyntaxNode::Error(t) => t,
+            SyntaxNode::Loop(l) => &l.open,
+        }
+    }
+}
+
+/// A loop with its brackets.
+#[derive(Debug)]
+pub struct SyntaxLoop {
+    /// The opening `[`.
+    pub open: SyntaxToken,
+    /// The nodes of the loop.
+    pub body: Vec<SyntaxNode>,
+    /// The closing ']', missing if the loop is unmatched.
+    pub close: Option<SyntaxToken>,
+}
+
+impl Drop for SyntaxLoop {
+    fn drop(&mut self) {
+        // Like ast::Body, tear nested loops down iteratively.
+        let mut stack = std::mem::take(&mut self.body);
+        while let Some(node) = stack.pop() {
+            if let SyntaxNode::Loop(mut l) = node {
+                stack.append(&mut l.body);
+            }
+        }
+    }
+}
+
+/// A lossless syntax tree: writing out its tokens and trivia in order
+/// reproduces the source it was parsed from byte for byte.
+#[derive(Debug)]
+pub struct SyntaxTree {
+    /// The nodes of the program.
+    pub nodes: Vec<SyntaxNode>,
+    /// The end of the program. Its text is `!` if the program ended at an
+    /// input separator; its leading trivia is the trivia at the end of the
+    /// file.
+    pub eof: SyntaxToken,
+    /// The bytes after the input separator.
+    pub input: Vec<u8>,
+}
+
+/// An iterator over the tokens of a [`SyntaxTree`] in source order.
+pub struct Tokens<'a> {
+    // node lists being visited, with the ']' to yield when a list is done
+    stack: Vec<(slice::Iter<'a, SyntaxNode>, Option<&'a SyntaxToken>)>,
+    eof: Option<&'a SyntaxToken>,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a SyntaxToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((nodes, close)) = self.stack.last_mut() {
+            match nodes.next() {
+                Some(SyntaxNode::Command(t) | SyntaxNode::Error(t)) => return Some(t),
+                Some(SyntaxNode::Loop(l)) => {
+                    self.stack.push((l.body.iter(), l.close.as_ref()));
+                    return Some(&l.open);
+                }
+                None => {
+                    let close = *close;
+                    self.stack.pop();
+                    if close.is_some() {
+                        return close;
+                    }
+                }
+            }
+        }
+        self.eof.take()
+    }
+}
+
+// A loop whose body is being converted by SyntaxTree::to_ast.
+struct Frame<'a> {
+    nodes: slice::Iter<'a, SyntaxNode>,
+    list: Vec<ast::Node>,
+    lp: Option<&'a SyntaxLoop>,
+}
+
+impl SyntaxTree {
+    /// Returns all tokens, including the final EOF token.
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens {
+            stack: vec![(self.nodes.iter(), None)],
+            eof: Some(&self.eof),
+        }
+    }
+
+    /// Returns the source text of the tree.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for t in self.tokens() {
+            for trivia in &t.leading {
+                out.extend_from_slice(&trivia.text);
+            }
+            out.extend_from_slice(&t.text);
+            for trivia in &t.trailing {
+                out.extend_from_slice(&trivia.text);
+            }
+        }
+        out.extend_from_slice(&self.input);
+        out
+    }
+
+    /// Derives the abstract syntax tree, which is the same tree the parser
+    /// produces for the source.
+    pub fn to_ast(&self) -> ast::Node {
+        let mut stack = vec![Frame {
+            nodes: self.nodes.iter(),
+            list: Vec::new(),
+            lp: None,
+        }];
+
+        loop {
+            let frame = stack.last_mut().unwrap();
+            match frame.nodes.next() {
+                Some(SyntaxNode::Command(t)) => frame.list.push(command(t)),
+                Some(SyntaxNode::Error(t)) => frame.list.push(ast::Node::BadNode(ast::BadNode {
+                    from: t.pos,
+                    to: t.end(),
+                })),
+                Some(SyntaxNode::Loop(l)) => stack.push(Frame {
+                    nodes: l.body.iter(),
+                    list: Vec::new(),
+                    lp: Some(l),
+                }),
+                None => {
+                    let frame = stack.pop().unwrap();
+                    let Some(l) = frame.lp else {
+                        return self.program(frame.list);
+                    };
+
+                    // The parser positions a body at the token after '['.
+                    let body_pos = l
+                        .body
+                        .first()
+                        .map(|n| n.first_token().pos)
+                        .or(l.close.as_ref().map(|t| t.pos))
+                        .unwrap_or(self.eof.pos);
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .list
+                        .push(ast::Node::Loop(ast::Loop {
+                            pos: l.open.pos,
+                            body: Box::new(ast::Node::Body(ast::Body {
+                                pos: body_pos,
+                                list: frame.list,
+                            })),
+                            rbrack: l.close.as_ref().map_or(token::NO_POS, |t| t.pos),
+                        }));
+                }
+            }
+        }
+    }
+
+    fn program(&self, list: Vec<ast::Node>) -> ast::Node {
+        let comments = self
+            .tokens()
+            .flat_map(|t| t.leading.iter().chain(&t.trailing))
+            .filter(|trivia| trivia.kind == TriviaKind::Comment)
+            .map(|trivia| ast::Comment {
+                pos: trivia.pos,
+                end: trivia.pos + trivia.text.len(),
+                text: String::from_utf8_lossy(&trivia.text).into_owned(),
+            })
+            .collect();
+
+        // The tree does not know its source, but the first token, which may
+        // be EOF, starts the file unless it has leading trivia.
+        let first = self.tokens().next().unwrap();
+        let pos = first.leading.first().map_or(first.pos, |trivia| trivia.pos);
+        let body_pos = list.first().map_or(self.eof.pos, |n| n.pos());
+
+        ast::Node::Program(ast::Program {
+            pos,
+            body: Box::new(ast::Node::Body(ast::Body {
+                pos: body_pos,
+                list,
+            })),
+            eof: self.eof.pos,
+            input: self.input.clone(),
+            comments,
+        })
+    }
+}
+
+fn command(t: &SyntaxToken) -> ast::Node {
+    let pos = t.pos;
+    match t.kind {
+        Token::IncPtr => ast::Node::IncPtr(ast::IncPtr { pos }),
+        Token::DecPtr => ast::Node::DecPtr(ast::DecPtr { pos }),
+        Token::IncByte => ast::Node::IncByte(ast::IncByte { pos }),
+        Token::DecByte => ast::Node::DecByte(ast::DecByte { pos }),
+        Token::OutputByte => ast::Node::OutputByte(ast::OutputByte { pos }),
+        Token::InputByte => ast::Node::InputByte(ast::InputByte { pos }),
+        Token::DebugDump => ast::Node::DebugDump(ast::DebugDump { pos }),
+        _ => ast::Node::BadNode(ast::BadNode {
+            from: pos,
+            to: t.end(),
+        }),
+    }
+}
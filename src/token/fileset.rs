@@ -0,0 +1,67 @@
+use crate::token::{Pos, Position, Source};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A set of source files sharing one Pos space. Every file is assigned a
+/// base offset so that Pos values are unique across the set and never
+/// collide with NO_POS.
+pub struct FileSet {
+    files: RefCell<Vec<Rc<Source>>>,
+}
+
+impl FileSet {
+    /// Returns an empty set.
+    pub fn new() -> Self {
+        Self {
+            files: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the base the next added file will get. The base is computed
+    /// from the last file, so a streamed file that is still growing pushes
+    /// it forward.
+    pub fn base(&self) -> usize {
+        self.files
+            .borrow()
+            .last()
+            .map_or(1, |last| last.base() + last.size() + 1)
+    }
+
+    /// Adds a file of the given size to the set. The position just past the
+    /// end of the file is valid as well, so the next file starts one byte
+    /// later.
+    pub fn add_file(&self, filename: impl Into<String>, size: usize) -> Rc<Source> {
+        let source = Rc::new(Source::new(filename, self.base(), size));
+        self.files.borrow_mut().push(source.clone());
+        source
+    }
+
+    /// Returns the file containing p, if any.
+    pub fn file(&self, p: Pos) -> Option<Rc<Source>> {
+        if !p.is_valid() {
+            return None;
+        }
+
+        let p: usize = p.into();
+        let files = self.files.borrow();
+        let i = files.partition_point(|f| f.base() <= p);
+        let file = files.get(i.checked_sub(1)?)?;
+        (p <= file.base() + file.size()).then(|| file.clone())
+    }
+
+    /// Returns the position of p, or an invalid position if no file contains p.
+    pub fn position(&self, p: Pos) -> Position {
+        self.file(p).map_or_else(Position::default, |f| f.position(p))
+    }
+
+    /// Returns the files in the order they were added.
+    pub fn files(&self) -> Vec<Rc<Source>> {
+        self.files.borrow().clone()
+    }
+}
+
+impl Default for FileSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
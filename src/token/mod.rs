@@ -1,7 +1,12 @@
+//! Tokens and positions in source files.
+
+mod fileset;
 mod position;
 mod source;
+#[allow(clippy::module_inception)]
 mod token;
 
+pub use fileset::*;
 pub use position::*;
 pub use source::*;
 pub use token::*;
@@ -1,14 +1,34 @@
-use std::fmt::{Display, Formatter, Write};
-use std::ops::{Add, Not};
+use std::fmt::{Display, Formatter};
+use std::ops::Add;
 
+/// The unit a column is counted in. Editors differ: most count bytes or
+/// Unicode scalar values, while LSP clients default to UTF-16 code units.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnUnit {
+    /// Bytes.
+    #[default]
+    Byte,
+    /// Unicode scalar values.
+    Char,
+    /// UTF-16 code units.
+    Utf16,
+}
+
+/// A position resolved to a file, line and column.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Position {
+    /// The name of the file, if any.
+    pub filename: String,
+    /// 0-based byte offset in the file.
     pub offset: usize,
+    /// 1-based line number, or 0 if unknown.
     pub line: usize,
+    /// 1-based column number, or 0 if unknown.
     pub column: usize,
 }
 
 impl Position {
+    /// Reports whether the position has a line.
     pub fn is_valid(&self) -> bool {
         self.line > 0
     }
@@ -16,24 +36,32 @@ impl Position {
 
 impl Display for Position {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.is_valid() {
-            if self.column != 0 {
-                write!(f, "{}:{}", self.line, self.column)
-            } else {
-                write!(f, "{}", self.line)
-            }
+        if !self.is_valid() {
+            return f.write_str(if self.filename.is_empty() { "-" } else { &self.filename });
+        }
+
+        if !self.filename.is_empty() {
+            write!(f, "{}:", self.filename)?;
+        }
+
+        if self.column != 0 {
+            write!(f, "{}:{}", self.line, self.column)
         } else {
-            write!(f, "-")
+            write!(f, "{}", self.line)
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// A compact position in a [`FileSet`](crate::token::FileSet). Positions order
+/// like the source they point into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct Pos(pub usize);
 
+/// The zero Pos, which is not a valid position.
 pub const NO_POS: Pos = Pos(0);
 
 impl Pos {
+    /// Reports whether p is not NO_POS.
     pub fn is_valid(self) -> bool {
         self != NO_POS
     }
@@ -47,9 +75,9 @@ impl<T: Into<usize>> Add<T> for Pos {
     }
 }
 
-impl Into<usize> for Pos {
-    fn into(self) -> usize {
-        self.0
+impl From<Pos> for usize {
+    fn from(pos: Pos) -> Self {
+        pos.0
     }
 }
 
@@ -1,35 +1,105 @@
-use crate::token::{Pos, Position};
+use crate::token::{ColumnUnit, Pos, Position};
+use std::ops::Range;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+/// A source file of a [`FileSet`](crate::token::FileSet), with its line table.
 pub struct Source {
-    size: usize,
+    name: String,
+    base: usize,
+    size: AtomicUsize,
     lines: Mutex<Vec<usize>>,
 }
 
 impl Source {
-    pub fn new(size: usize) -> Self {
+    /// Creates a source for the file `name` whose first byte has position
+    /// `base`. Sources are normally created with [`crate::token::FileSet::add_file`].
+    pub fn new(name: impl Into<String>, base: usize, size: usize) -> Self {
         Self {
-            size,
+            name: name.into(),
+            base,
+            size: AtomicUsize::new(size),
             lines: Mutex::new(vec![0]),
         }
     }
 
+    /// Returns the name of the file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the position of the first byte of the file.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Returns the size of the file in bytes.
     pub fn size(&self) -> usize {
-        self.size
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Grows the source to at least size bytes. Streaming scanners use this
+    /// to extend the source as input arrives; in a FileSet only the most
+    /// recently added file may grow, or its positions would overlap the
+    /// next file's.
+    pub fn grow_to(&self, size: usize) {
+        self.size.fetch_max(size, Ordering::Relaxed);
+    }
+
+    /// Updates the size and line table for an edit that replaces the bytes
+    /// in range with text. Lines after the edit are shifted rather than
+    /// rescanned. As with grow_to, only the most recently added file of a
+    /// FileSet may grow.
+    pub fn apply_edit(&self, range: Range<usize>, text: &[u8]) {
+        let size = self.size();
+        if range.start > range.end || range.end > size {
+            panic!(
+                "invalid edit range {:?} (should be within [0, {}])",
+                range, size
+            );
+        }
+
+        let new_size = size - range.len() + text.len();
+        let mut lines = self.lines.lock().unwrap();
+        // Lines starting in (start, end] followed a newline that is replaced.
+        let first = lines.partition_point(|&start| start <= range.start);
+        let last = lines.partition_point(|&start| start <= range.end);
+        let shifted: Vec<usize> = lines[last..]
+            .iter()
+            .map(|&start| start - range.len() + text.len())
+            .collect();
+
+        lines.truncate(first);
+        if first > 1 && lines[first - 1] == new_size {
+            // The line now starts at the end of the file.
+            lines.pop();
+        }
+        for (i, &b) in text.iter().enumerate() {
+            let start = range.start + i + 1;
+            if b == b'\n' && start < new_size {
+                lines.push(start);
+            }
+        }
+        lines.extend(shifted);
+        self.size.store(new_size, Ordering::Relaxed);
     }
 
+    /// Returns the number of lines.
     pub fn line_count(&self) -> usize {
         self.lines.lock().unwrap().len()
     }
 
+    /// Adds a line starting at offset. It is ignored if it does not follow the
+    /// last line or is past the end of the file.
     pub fn add_line(&self, offset: usize) {
         let mut lines = self.lines.lock().unwrap();
         let i = lines.len();
-        if (i == 0 || lines[i - 1] < offset) && offset < self.size {
+        if (i == 0 || lines[i - 1] < offset) && offset < self.size() {
             lines.push(offset);
         }
     }
 
+    /// Merges line with the line that follows it.
     pub fn merge_lines(&self, line: usize) {
         if line < 1 {
             panic!("invalid line number {} (should be >= 1)", line);
@@ -43,10 +113,11 @@ impl Source {
         lines.remove(line);
     }
 
+    /// Sets the line table, returning false if lines is not a valid table.
     pub fn set_lines(&self, lines: Vec<usize>) -> bool {
         // Verify the validity of the line table
         for i in 1..lines.len() {
-            if lines[i] <= lines[i - 1] || self.size <= lines[i] {
+            if lines[i] <= lines[i - 1] || self.size() <= lines[i] {
                 return false;
             }
         }
@@ -56,9 +127,10 @@ impl Source {
         true
     }
 
+    /// Sets the line table from the content of the file.
     pub fn set_lines_for_content(&self, content: &[u8]) {
         let mut lines: Vec<usize> = vec![];
-        let mut line: Option<usize> = None;
+        let mut line: Option<usize> = Some(0);
         for (offset, &b) in content.iter().enumerate() {
             if let Some(line) = line {
                 lines.push(line);
@@ -74,6 +146,7 @@ impl Source {
         *guard = lines;
     }
 
+    /// Returns the position of the first byte of line.
     pub fn line_start(&self, line: usize) -> Pos {
         if line < 1 {
             panic!("invalid line number {} (should be >= 1)", line);
@@ -84,32 +157,75 @@ impl Source {
             panic!("invalid line number {} (should be < {})", line, lines.len());
         }
 
-        lines[line - 1].into()
+        self.pos(lines[line - 1])
+    }
+
+    /// Returns the Pos value for the given file offset.
+    pub fn pos(&self, offset: usize) -> Pos {
+        if offset > self.size() {
+            panic!("invalid file offset {} (should be <= {})", offset, self.size());
+        }
+
+        Pos(self.base + offset)
     }
 
+    /// Returns the file offset for the given Pos, which must belong to this
+    /// source.
+    pub fn offset(&self, p: Pos) -> usize {
+        let p: usize = p.into();
+        if p < self.base || p > self.base + self.size() {
+            panic!("invalid Pos value {} (should be in [{}, {}])", p, self.base, self.base + self.size());
+        }
+
+        p - self.base
+    }
+
+    /// Returns the line of p.
     pub fn line(&self, p: Pos) -> usize {
         self.position(p).line
     }
 
+    /// Returns the 1-based line and byte column of offset.
     pub fn unpack(&self, offset: usize) -> (usize, usize) {
         let lines = self.lines.lock().unwrap();
-        lines
-            .iter()
-            .position(|&i| i == offset)
-            .map_or((0, 0), |i| (i + 1, offset - lines[i]))
+        match lines.partition_point(|&start| start <= offset) {
+            0 => (0, 0),
+            i => (i, offset - lines[i - 1] + 1),
+        }
     }
 
+    /// Returns the position of p, with columns in bytes.
     pub fn position(&self, p: Pos) -> Position {
         if !p.is_valid() {
             return Position::default();
         }
 
-        let (line, column) = self.unpack(p.into());
+        let offset = self.offset(p);
+        let (line, column) = self.unpack(offset);
 
         Position {
-            offset: p.into(),
+            filename: self.name.clone(),
+            offset,
             line,
             column,
         }
     }
+
+    /// Like position, but counts the column in the given unit. content must
+    /// be the text the source was created for.
+    pub fn position_in(&self, p: Pos, content: &[u8], unit: ColumnUnit) -> Position {
+        let mut position = self.position(p);
+        if position.is_valid() && unit != ColumnUnit::Byte {
+            let start = position.offset + 1 - position.column;
+            let prefix = String::from_utf8_lossy(&content[start..position.offset]);
+            let width = match unit {
+                ColumnUnit::Byte => unreachable!(),
+                ColumnUnit::Char => prefix.chars().count(),
+                ColumnUnit::Utf16 => prefix.encode_utf16().count(),
+            };
+            position.column = width + 1;
+        }
+
+        position
+    }
 }
@@ -1,18 +1,34 @@
 use std::fmt;
 use std::fmt::Formatter;
 
+/// The lexical tokens of Brainfuck.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token {
+    /// A character that is not allowed.
     ILLEGAL,
+    /// The end of the program.
     EOF,
+    /// `>`
     IncPtr,
+    /// `<`
     DecPtr,
+    /// `+`
     IncByte,
+    /// `-`
     DecByte,
+    /// `.`
     OutputByte,
+    /// `,`
     InputByte,
+    /// `[`
     LoopOpen,
+    /// `]`
     LoopClose,
+    /// `#`
+    DebugDump,
+    /// A run of non-command characters.
+    Comment,
+    /// A token of an unknown kind.
     Unknown(i32),
 }
 
@@ -29,6 +45,8 @@ impl fmt::Display for Token {
             Token::InputByte => ",",
             Token::LoopOpen => "[",
             Token::LoopClose => "]",
+            Token::DebugDump => "#",
+            Token::Comment => "COMMENT",
             Token::Unknown(tok) => return write!(f, "token({})", tok),
         };
 
@@ -0,0 +1,145 @@
+use rust_brainfuck::ast::{
+    Event, Node, Program, Visitor, VisitorMut, apply, fprint_sexpr, inspect, walk, walk_mut,
+};
+use rust_brainfuck::bf;
+use rust_brainfuck::parser::parse_program_from;
+use std::cell::Cell;
+
+fn parse(src: &str) -> Node {
+    parse_program_from(src).unwrap()
+}
+
+fn sexpr(node: &Node) -> String {
+    let mut out = String::new();
+    fprint_sexpr(&mut out, None, node).unwrap();
+    out
+}
+
+struct CountLoops(Cell<usize>);
+
+impl Visitor for CountLoops {
+    fn visit(&self, node: &Node) -> Option<&dyn Visitor> {
+        if let Node::Loop(_) = node {
+            self.0.set(self.0.get() + 1);
+        }
+        Some(self)
+    }
+}
+
+#[test]
+fn walk_and_inspect() {
+    let node = parse("+[>[-]<[-]]");
+    let counter = CountLoops(Cell::new(0));
+    walk(&counter, &node);
+    assert_eq!(counter.0.get(), 3);
+
+    let mut max_depth = 0;
+    inspect(&node, |_, event| {
+        if let Event::Enter { depth } = event {
+            max_depth = max_depth.max(depth);
+        }
+        true
+    });
+    // Program, Body, Loop, Body, Loop, Body, DecByte
+    assert_eq!(max_depth, 6);
+}
+
+// Turns every `+` into a `-`.
+struct Negate;
+
+impl VisitorMut for Negate {
+    fn enter(&mut self, node: &mut Node) -> bool {
+        if let Node::IncByte(n) = node {
+            *node = Node::DecByte(rust_brainfuck::ast::DecByte { pos: n.pos });
+        }
+        true
+    }
+}
+
+#[test]
+fn rewrite() {
+    let mut node = parse("+[+-]");
+    walk_mut(&mut Negate, &mut node);
+    assert_eq!(node, parse("-[--]"));
+
+    // Remove every loop.
+    apply(
+        &mut node,
+        |cursor| {
+            if let Node::Loop(_) = cursor.node() {
+                cursor.delete();
+                return false;
+            }
+            true
+        },
+        |_| true,
+    );
+    assert_eq!(node, parse("-"));
+}
+
+#[test]
+fn build() {
+    assert_eq!(bf!(++[->+<]).to_json(), parse("++[->+<]").to_json());
+    assert_eq!(bf!(,[.,]).to_json(), parse(",[.,]").to_json());
+
+    let program = Program::builder()
+        .add(3)
+        .loop_(|b| b.sub(1).right(1).add(2).left(1))
+        .right(1)
+        .output()
+        .build();
+    assert_eq!(
+        Node::from(program).to_json(),
+        parse("+++[->++<]>.").to_json()
+    );
+}
+
+#[test]
+fn equality_and_hash() {
+    let a = parse("+[->+<]");
+    let b = parse("  +  [ - > + < ]  ");
+    assert_eq!(a, b);
+    assert_eq!(a.stable_hash(), b.stable_hash());
+    assert_eq!(a.clone(), a);
+
+    let c = parse("+[->-<]");
+    assert_ne!(a, c);
+    assert_ne!(a.stable_hash(), c.stable_hash());
+}
+
+#[test]
+fn deep_nesting() {
+    let depth = 100_000;
+    let src = "[".repeat(depth) + &"]".repeat(depth);
+    let node = parse(&src);
+    let copy = node.clone();
+    assert_eq!(node, copy);
+
+    let mut loops = 0;
+    inspect(&node, |node, event| {
+        if let (Node::Loop(_), Event::Enter { .. }) = (node, event) {
+            loops += 1;
+        }
+        true
+    });
+    assert_eq!(loops, depth);
+    let json = node.to_json();
+    assert_eq!(Node::from_json(&json).unwrap().to_json(), json);
+}
+
+#[test]
+fn json() {
+    let node = parse("+[-]>.");
+    let json = node.to_json();
+    assert_eq!(Node::from_json(&json).unwrap().to_json(), json);
+    assert!(Node::from_json("{").is_err());
+    assert!(Node::from_json(&(json + "{}")).is_err());
+}
+
+#[test]
+fn print() {
+    assert_eq!(
+        sexpr(&parse("+[-]")),
+        "(Program (Body IncByte (Loop (Body DecByte))))\n"
+    );
+}
@@ -0,0 +1,70 @@
+use rust_brainfuck::parser::parse_file;
+use rust_brainfuck::scanner::{Code, ErrorList, Level, LintLevels, Mode, Renderer, Severity};
+use rust_brainfuck::token::FileSet;
+
+fn errors(filename: &str, src: &str) -> ErrorList {
+    let fset = FileSet::new();
+    let err = parse_file(&fset, filename, src, Mode::default()).unwrap_err();
+    err.downcast::<ErrorList>().map(|list| *list).unwrap()
+}
+
+#[test]
+fn render() {
+    let src = "++[->+<";
+    let list = errors("hello.bf", src);
+    let mut renderer = Renderer::new();
+    renderer.add_file("hello.bf", src.as_bytes());
+    let out = renderer.render_list(&list);
+    assert!(
+        out.starts_with("error[BF0002]: unmatched '['\n --> hello.bf:1:3\n"),
+        "{}",
+        out
+    );
+    assert!(out.contains("1 | ++[->+<\n"), "{}", out);
+    assert!(!out.contains('\x1b'));
+
+    renderer.set_color(true);
+    assert!(renderer.render_list(&list).contains('\x1b'));
+}
+
+#[test]
+fn codes() {
+    assert_eq!(Code::UNMATCHED_OPEN.to_string(), "BF0002");
+    assert_eq!("BF0001".parse::<Code>().unwrap(), Code::UNMATCHED_CLOSE);
+    assert!("BF".parse::<Code>().is_err());
+    for code in Code::all() {
+        assert!(code.summary().is_some(), "{}", code);
+        assert_eq!(code.to_string().parse::<Code>().unwrap(), code);
+    }
+}
+
+#[test]
+fn lint_levels() {
+    let mut list = errors("", "+]");
+    assert_eq!(list.iter().next().unwrap().severity, Severity::Error);
+
+    let mut levels = LintLevels::new();
+    levels.set(Code::UNMATCHED_CLOSE, Level::Warn);
+    levels.apply(&mut list);
+    assert_eq!(list.iter().next().unwrap().severity, Severity::Warning);
+    assert!(!list.has_errors());
+
+    levels.set(Code::UNMATCHED_CLOSE, Level::Allow);
+    levels.apply(&mut list);
+    assert!(list.is_empty());
+}
+
+#[test]
+fn fixes() {
+    let src = "+]+[-";
+    let list = errors("a.bf", src);
+    let (fixed, applied) = list.apply_fixes("a.bf", src.as_bytes());
+    assert_eq!(applied, list.len());
+    let fixed = String::from_utf8(fixed).unwrap();
+    let fset = FileSet::new();
+    assert!(
+        parse_file(&fset, "a.bf", fixed.as_str(), Mode::default()).is_ok(),
+        "{}",
+        fixed
+    );
+}
@@ -0,0 +1,58 @@
+use rust_brainfuck::format::{self, Config};
+use rust_brainfuck::parser::parse_program_with_mode;
+use rust_brainfuck::scanner::{Code, Mode};
+
+fn fmt(src: &str) -> String {
+    String::from_utf8(format::source(src.as_bytes(), Mode::default()).unwrap()).unwrap()
+}
+
+#[test]
+fn layout() {
+    assert_eq!(fmt("+ + [ - ]"), "++[-]\n");
+    assert_eq!(fmt("+[>[-]<-]"), "+[\n  >[-]<-\n]\n");
+    assert_eq!(fmt("+ # add\n# done\n"), "+ # add\n# done\n");
+}
+
+#[test]
+fn idempotent() {
+    let srcs = [
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+        "# set up\n+++ # three\n[ # loop\n  - # dec\n]\n",
+        ",[.,]",
+        "",
+    ];
+    let config = Config {
+        width: 20,
+        indent: 4,
+    };
+    for src in srcs {
+        let once = config.source(src.as_bytes(), Mode::default()).unwrap();
+        assert_eq!(config.source(&once, Mode::default()).unwrap(), once);
+        assert!(config.check(&once, Mode::default()).unwrap());
+        assert!(
+            once.split(|&b| b == b'\n')
+                .all(|line| line.len() <= 20 || line.contains(&b'#'))
+        );
+
+        let mode = Mode::SCAN_COMMENTS;
+        let before = parse_program_with_mode(src, mode).unwrap();
+        let after = parse_program_with_mode(once.as_slice(), mode).unwrap();
+        assert_eq!(before, after);
+    }
+}
+
+#[test]
+fn check() {
+    assert!(!Config::default().check(b"+ +", Mode::default()).unwrap());
+    assert!(Config::default().check(b"++\n", Mode::default()).unwrap());
+
+    let err = format::source(b"+]", Mode::default()).unwrap_err();
+    assert_eq!(err.iter().next().unwrap().code, Some(Code::UNMATCHED_CLOSE));
+}
+
+#[test]
+fn input() {
+    let src = "+ , .!a b\n";
+    let out = format::source(src.as_bytes(), Mode::INPUT_SEPARATOR).unwrap();
+    assert_eq!(out, b"+,.\n!a b\n");
+}
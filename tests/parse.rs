@@ -0,0 +1,140 @@
+use rust_brainfuck::ast::{Node, Spanned};
+use rust_brainfuck::parser::{
+    IncrementalParser, Parser, parse_file, parse_program_from, parse_program_with_mode,
+};
+use rust_brainfuck::scanner::{Code, Edit, ErrorList, LexemeKind, Mode, tokenize};
+use rust_brainfuck::token::{FileSet, Source, Token};
+use rust_brainfuck::{arena, syntax};
+use std::rc::Rc;
+
+fn program(node: &Node) -> &rust_brainfuck::ast::Program {
+    match node {
+        Node::Program(program) => program,
+        _ => panic!("not a program: {:?}", node),
+    }
+}
+
+fn codes(list: &ErrorList) -> Vec<Code> {
+    list.iter().filter_map(|err| err.code).collect()
+}
+
+#[test]
+fn parse_program() {
+    let node = parse_program_from("+[->+<]").unwrap();
+    let Node::Body(body) = &*program(&node).body else {
+        panic!("program without a body");
+    };
+    assert_eq!(body.list.len(), 2);
+    let Node::Loop(l) = &body.list[1] else {
+        panic!("expected a loop, found {:?}", body.list[1]);
+    };
+    assert_eq!(l.pos, l.pos());
+    assert_eq!(l.end(), l.rbrack + 1usize);
+}
+
+#[test]
+fn positions() {
+    let fset = FileSet::new();
+    let node = parse_file(&fset, "a.bf", "+\n [-]", Mode::default()).unwrap();
+    let Node::Body(body) = &*program(&node).body else {
+        panic!("program without a body");
+    };
+    let position = fset.position(body.list[1].pos());
+    assert_eq!(position.to_string(), "a.bf:2:2");
+}
+
+#[test]
+fn errors() {
+    let err = parse_program_from("]+[").unwrap_err();
+    let list = err.downcast_ref::<ErrorList>().unwrap();
+    assert_eq!(
+        codes(list),
+        vec![Code::UNMATCHED_CLOSE, Code::UNMATCHED_OPEN]
+    );
+
+    let fixed = list.apply_fixes("", b"]+[").0;
+    assert!(parse_program_from(fixed.as_slice()).is_ok());
+}
+
+#[test]
+fn error_limit() {
+    let src = b"]]]]";
+    let mut parser = Parser::new(Rc::new(Source::new("", 1, src.len())), src, Mode::default());
+    parser.set_error_limit(Some(2));
+    parser.parse_program();
+    let errors = parser.errors();
+    assert_eq!(
+        codes(&errors),
+        vec![
+            Code::UNMATCHED_CLOSE,
+            Code::UNMATCHED_CLOSE,
+            Code::TOO_MANY_ERRORS
+        ]
+    );
+}
+
+#[test]
+fn input_and_comments() {
+    let mode = Mode::INPUT_SEPARATOR | Mode::SCAN_COMMENTS;
+    let node = parse_program_with_mode("+ add one\n.!data", mode).unwrap();
+    let program = program(&node);
+    assert_eq!(program.input, b"data");
+    assert_eq!(program.comments.len(), 1);
+    assert_eq!(program.comments[0].text, "add one");
+}
+
+#[test]
+fn incremental() {
+    let text = b"+[->+<]\n[-]".to_vec();
+    let source = Rc::new(Source::new("", 1, text.len()));
+    let mut parser = IncrementalParser::new(source, text, Mode::default());
+    parser.apply_edit(&Edit::insert(3, ">>"));
+    assert_eq!(parser.text(), b"+[->>>+<]\n[-]");
+
+    let reparsed = parse_program_from(parser.text()).unwrap();
+    assert_eq!(Node::Program(parser.program().clone()), reparsed);
+    assert!(parser.errors().is_empty());
+}
+
+#[test]
+fn syntax_tree() {
+    let src = b" +[ - ] # comment\n";
+    let source = Rc::new(Source::new("", 1, src.len()));
+    let (tree, errors) = syntax::parse(source, src, Mode::SCAN_COMMENTS);
+    assert!(errors.is_empty());
+    assert_eq!(tree.to_bytes(), src);
+    assert_eq!(
+        tree.to_ast(),
+        parse_program_with_mode(&src[..], Mode::SCAN_COMMENTS).unwrap()
+    );
+}
+
+#[test]
+fn tokens() {
+    let src = b"+ [-]\n";
+    let lexemes: Vec<_> = tokenize(src).collect::<Result<_, _>>().unwrap();
+    assert_eq!(lexemes[0].kind, LexemeKind::Token(Token::IncByte));
+
+    let text: Vec<u8> = lexemes
+        .iter()
+        .flat_map(|l| src[l.span.clone()].to_vec())
+        .collect();
+    assert_eq!(text, src);
+}
+
+#[test]
+fn arena() {
+    let src = b"++[>+[-]<-]";
+    let mut parser = Parser::new(Rc::new(Source::new("", 1, src.len())), src, Mode::default());
+    let tree = parser.parse_arena();
+    assert_eq!(tree.nodes().len(), 9);
+    assert_eq!(tree.body().count(), 3);
+
+    let loop_id = tree.body().nth(2).unwrap();
+    assert_eq!(tree.node(loop_id).kind, arena::Kind::Loop);
+    assert_eq!(tree.children(loop_id).count(), 5);
+    assert_eq!(
+        Node::Program(tree.to_ast()),
+        parse_program_from(&src[..]).unwrap()
+    );
+}